@@ -0,0 +1,6720 @@
+//! Validation and generation of standardised Git commit messages.
+//!
+//! [`CommitMessageVerifier`] checks a message against the rules enabled in a
+//! [`Config`]. [`GitDiffAnalyzer`] reads the staged changes into
+//! [`GitChanges`], and [`CommitMessageGenerator`] writes a message from them.
+
+use clap::ValueEnum;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use log::warn;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use strsim::damerau_levenshtein;
+use textwrap::{fill, Options};
+
+/// Settings read from `.scripture.toml`, layered over the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub version: u32,
+    pub standard_verbs: BTreeMap<String, String>,
+    pub indicators: BTreeMap<String, Vec<String>>,
+    pub verb_mapping: BTreeMap<String, String>,
+    pub message_template: MessageTemplate,
+    pub custom_messages: BTreeMap<String, String>,
+    pub rule_severities: BTreeMap<String, Severity>,
+    pub test_advisory: bool,
+    pub source_file_patterns: Vec<String>,
+    pub test_file_patterns: Vec<String>,
+    pub require_edited_sections: bool,
+    pub fail_fast: bool,
+    pub check_description_mood: bool,
+    pub check_subject_mood: bool,
+    pub max_body_lines: Option<usize>,
+    pub required_footers: BTreeMap<String, Vec<String>>,
+    pub require_sign_off: bool,
+    pub subject_max_words: Option<usize>,
+    pub standalone_subjects: Vec<String>,
+    pub doc_file_patterns: Vec<String>,
+    pub check_category_verb: bool,
+    pub category_verbs: BTreeMap<String, String>,
+    pub check_footer_grammar: bool,
+    #[serde(alias = "subject_max_len")]
+    pub subject_max_length: usize,
+    #[serde(alias = "body_max_len")]
+    pub body_max_line_length: usize,
+    pub history_count: usize,
+    pub binary_size_limit: u64,
+    pub strip_prefixes: Vec<String>,
+    pub count_prefixes_in_length: bool,
+    pub require_blank_after_subject: bool,
+    pub include_excerpt: bool,
+    pub excerpt_lines: usize,
+    pub require_final_newline: bool,
+    pub check_trailing_whitespace: bool,
+    pub check_tabs: bool,
+    pub allow_merge_commits: bool,
+    pub git_timeout_secs: u64,
+    pub check_single_change: bool,
+    pub check_single_sentence: bool,
+    pub duplicate_subject_lookback: Option<usize>,
+    pub body_wrap: BodyWrap,
+    pub wrap_generated_body: bool,
+    pub reference_keywords: Vec<String>,
+    pub default_branch: Option<String>,
+    pub convention: Convention,
+    pub strict_capitalisation: bool,
+    pub conventional_types: Vec<String>,
+    pub branch_ticket_pattern: String,
+    pub gitmoji: bool,
+    pub gitmoji_map: BTreeMap<String, String>,
+    pub group_depth: Option<usize>,
+    pub changes_per_file: usize,
+    pub max_listed_files: Option<usize>,
+    pub changelog_sections: BTreeMap<String, String>,
+}
+
+/// Which style subjects are verified against: a standard verb, or a
+/// Conventional Commits `type(scope)!: description` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Convention {
+    /// Subjects start with a standard verb
+    Imperative,
+    /// Subjects follow the Conventional Commits spec
+    Conventional,
+}
+
+/// Whether body prose is expected to be wrapped by hand or left for the
+/// viewer to soft-wrap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyWrap {
+    Hard,
+    Soft,
+}
+
+/// The sections a generated message is made of. The first line of each is
+/// its heading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageTemplate {
+    pub references_section: String,
+    pub testing_section: String,
+    pub dependencies_section: String,
+    pub changes_section: String,
+    pub breaking_section: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub references_section_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub testing_section_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependencies_section_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes_section_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub breaking_section_file: Option<PathBuf>,
+}
+
+/// An organisation-wide verb vocabulary, read from the file named by
+/// `SCRIPTURE_VERB_POLICY`. When locked, repositories can't add verbs to it.
+#[derive(Debug, Deserialize)]
+pub struct VerbPolicy {
+    #[serde(default)]
+    locked: bool,
+    standard_verbs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StructuredMessage {
+    subject: String,
+    #[serde(default)]
+    body: String,
+}
+
+/// What was found in a diff, ready to be turned into a message.
+#[derive(Debug)]
+pub struct GitChanges {
+    pub file_changes: BTreeMap<String, Vec<String>>,
+    pub file_removals: BTreeMap<String, Vec<String>>,
+    /// Renamed files as `(from, to)` pairs, in diff order
+    pub renames: Vec<(String, String)>,
+    /// Binary files, whose content the diff doesn't show, with the change the
+    /// diff implies
+    pub binary_files: BTreeMap<String, FileStatus>,
+    /// Lines added and removed in each text file, blank lines included
+    pub line_counts: BTreeMap<String, (usize, usize)>,
+    pub breaking_changes: Vec<String>,
+    pub advisories: Vec<String>,
+    pub reference: Option<String>,
+    pub file_status: BTreeMap<String, FileStatus>,
+    pub related_commits: Vec<String>,
+    /// People credited with `Co-authored-by:` trailers, as "Name <email>"
+    pub co_authors: Vec<String>,
+    pub verb: Option<String>,
+}
+
+/// A verb the diff suggests, with the indicator words that matched it.
+#[derive(Debug, Serialize)]
+pub struct VerbCandidate {
+    pub verb: String,
+    pub matches: usize,
+    pub words: Vec<String>,
+}
+
+/// An indicator word found in one added line.
+#[derive(Debug, Serialize)]
+pub struct IndicatorMatch {
+    pub indicator: String,
+    pub word: String,
+    pub file: String,
+    pub line: String,
+    pub count: usize,
+}
+
+/// What settled the verb for a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VerbReason {
+    /// Picked by the author, e.g. with `--interactive`
+    Given,
+    AllAdded,
+    AllDeleted,
+    /// The verb whose indicator words matched the added lines most often
+    Indicators,
+    /// Nothing matched, so the diff is taken as an addition
+    Default,
+}
+
+/// The verb chosen for a diff and why: the matches behind it, and every
+/// candidate the indicator words suggested, best first.
+#[derive(Debug, Serialize)]
+pub struct VerbChoice {
+    pub verb: String,
+    pub reason: VerbReason,
+    pub matches: Vec<IndicatorMatch>,
+    pub candidates: Vec<VerbCandidate>,
+}
+
+/// How git reports a changed file in `--name-status` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+}
+
+impl FileStatus {
+    // Maps the letter git prints in `--name-status` output. Type changes and
+    // other rarer letters are treated as modifications.
+    fn from_letter(letter: char) -> Self {
+        match letter {
+            'A' => FileStatus::Added,
+            'D' => FileStatus::Deleted,
+            'R' => FileStatus::Renamed,
+            'C' => FileStatus::Copied,
+            _ => FileStatus::Modified,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            FileStatus::Added => "added",
+            FileStatus::Modified => "modified",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Renamed => "renamed",
+            FileStatus::Copied => "copied",
+        }
+    }
+}
+
+/// The broad kind of a changed file, as judged by the configured patterns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileCategory {
+    Docs,
+    Tests,
+    Source,
+    Other,
+}
+
+impl FileCategory {
+    fn key(&self) -> &'static str {
+        match self {
+            FileCategory::Docs => "docs",
+            FileCategory::Tests => "tests",
+            FileCategory::Source => "source",
+            FileCategory::Other => "other",
+        }
+    }
+}
+
+/// The semantic version component a change calls for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionBump {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl fmt::Display for VersionBump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VersionBump::Major => "major",
+            VersionBump::Minor => "minor",
+            VersionBump::Patch => "patch",
+        })
+    }
+}
+
+/// `current`, an `X.Y.Z` version with an optional `v` prefix, after `bump`;
+/// pre-release and build suffixes are dropped. With `zero_minor`, a major
+/// bump below 1.0.0 bumps the minor version instead, as Cargo reads them.
+pub fn next_version(current: &str, bump: VersionBump, zero_minor: bool) -> Result<String, String> {
+    let (prefix, version) = match current.strip_prefix('v') {
+        Some(version) => ("v", version),
+        None => ("", current),
+    };
+    let core = version.split(['-', '+']).next().unwrap_or("");
+    let parts: Vec<u64> = core
+        .split('.')
+        .map(|part| part.parse::<u64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("'{}' is not an X.Y.Z version", current))?;
+    let [major, minor, patch] = parts[..] else {
+        return Err(format!("'{}' is not an X.Y.Z version", current));
+    };
+
+    let (major, minor, patch) = match bump {
+        VersionBump::Major if major == 0 && zero_minor => (0, minor + 1, 0),
+        VersionBump::Major => (major + 1, 0, 0),
+        VersionBump::Minor => (major, minor + 1, 0),
+        VersionBump::Patch => (major, minor, patch + 1),
+    };
+    Ok(format!("{}{}.{}.{}", prefix, major, minor, patch))
+}
+
+// Paths left out of the analysis, read from a gitignore-style file. The last
+// pattern that matches a path decides, so `!` can re-include one.
+#[derive(Debug, Default)]
+struct IgnoreRules {
+    rules: Vec<(GlobSet, bool)>,
+}
+
+impl IgnoreRules {
+    // Follows gitignore: a pattern with a slash before its end is anchored at
+    // the root, one without matches at any depth, a trailing slash matches
+    // only directories, and a match on a directory covers everything in it.
+    fn parse(contents: &str) -> Self {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (directory, pattern) = match pattern.strip_suffix('/') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern),
+            };
+            let pattern = match pattern.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if pattern.contains('/') => pattern.to_string(),
+                None => format!("**/{}", pattern),
+            };
+
+            let mut variants = vec![format!("{}/**", pattern)];
+            if !directory {
+                variants.push(pattern);
+            }
+            let mut builder = GlobSetBuilder::new();
+            for variant in &variants {
+                match GlobBuilder::new(variant).literal_separator(true).build() {
+                    Ok(glob) => {
+                        builder.add(glob);
+                    }
+                    Err(e) => warn!("Ignoring invalid ignore pattern {:?}: {}", line, e),
+                }
+            }
+            if let Ok(set) = builder.build() {
+                rules.push((set, negated));
+            }
+        }
+        Self { rules }
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(set, _)| set.is_match(path))
+            .is_some_and(|(_, negated)| !negated)
+    }
+}
+
+// The linguist attributes `.gitattributes` sets on a file.
+#[derive(Debug, Default)]
+struct LinguistAttributes {
+    generated: bool,
+    vendored: bool,
+    language: Option<String>,
+}
+
+impl LinguistAttributes {
+    // Generated and vendored files aren't the author's work, so they are
+    // left out of the summary.
+    fn is_ignored(&self) -> bool {
+        self.generated || self.vendored
+    }
+}
+
+// Reads `git check-attr` output, whose lines read
+// "<path>: <attribute>: <value>".
+fn parse_linguist_attributes(output: &str) -> BTreeMap<String, LinguistAttributes> {
+    let mut attributes: BTreeMap<String, LinguistAttributes> = BTreeMap::new();
+    for line in output.lines() {
+        let mut fields = line.rsplitn(3, ": ");
+        let (Some(value), Some(attribute), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if value == "unspecified" {
+            continue;
+        }
+
+        let set = value == "set" || value == "true";
+        let entry = attributes.entry(unquote_path(path)).or_default();
+        match attribute {
+            "linguist-generated" => entry.generated = set,
+            "linguist-vendored" => entry.vendored = set,
+            "linguist-language" if value != "unset" => entry.language = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    attributes
+}
+
+impl GitChanges {
+    /// Whether the diff touched any file at all.
+    pub fn has_changes(&self) -> bool {
+        !self.file_changes.is_empty()
+            || !self.file_removals.is_empty()
+            || !self.renames.is_empty()
+            || !self.binary_files.is_empty()
+            || !self.file_status.is_empty()
+    }
+}
+
+/// A way in which a commit message breaks a rule.
+#[derive(Debug)]
+pub enum Violation {
+    EmptyMessage,
+    SubjectTooLong {
+        len: usize,
+        max: usize,
+    },
+    UnknownVerb {
+        verb: String,
+        allowed: Vec<(String, String)>,
+        suggestion: Option<String>,
+    },
+    MissingDescription {
+        verb: String,
+    },
+    SubjectFullStop,
+    SubjectNotCapitalised,
+    MissingBlankLine,
+    BodyLineTooLong {
+        line: usize,
+        len: usize,
+        max: usize,
+    },
+    UneditedSection {
+        section: String,
+    },
+    NonImperativeDescription {
+        word: String,
+        suggestion: String,
+    },
+    NonImperativeSubject {
+        word: String,
+        suggestion: Option<String>,
+    },
+    BodyTooLong {
+        count: usize,
+        max: usize,
+    },
+    MissingFooter {
+        verb: String,
+        footer: String,
+    },
+    MissingSignOff,
+    MalformedSignOff {
+        value: String,
+    },
+    SubjectTooManyWords {
+        count: usize,
+        max: usize,
+    },
+    ControlCharacter {
+        line: usize,
+        column: usize,
+        code: u32,
+    },
+    TrailingWhitespace {
+        line: usize,
+    },
+    TabCharacter {
+        line: usize,
+        column: usize,
+    },
+    CategoryVerbMismatch {
+        verb: String,
+        category: String,
+        expected: String,
+    },
+    MalformedFooter {
+        line: usize,
+        text: String,
+    },
+    FinalNewline {
+        count: usize,
+    },
+    MultipleChanges {
+        conjunction: String,
+        second: String,
+    },
+    DuplicateSubject {
+        subject: String,
+    },
+    MultipleSentences {
+        rest: String,
+    },
+    UnknownReferenceKeyword {
+        line: usize,
+        keyword: String,
+        allowed: Vec<String>,
+    },
+    MalformedHeader {
+        problem: String,
+    },
+}
+
+/// A rule's entry in the catalog shown by `scripture explain`.
+pub struct RuleInfo {
+    pub id: &'static str,
+    pub description: &'static str,
+    pub rationale: &'static str,
+    pub good: &'static str,
+    pub bad: &'static str,
+    pub severity: Severity,
+}
+
+/// Every rule the verifier knows, in the order they are checked.
+pub const RULES: &[RuleInfo] = &[
+    RuleInfo {
+        id: "empty-message",
+        description: "The commit message must not be empty.",
+        rationale: "An empty message tells reviewers and future readers nothing about the change.",
+        good: "Add retry to the upload client",
+        bad: "",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-length",
+        description: "The subject line must not exceed the configured length, 50 characters by default.",
+        rationale: "Short subjects stay readable in `git log --oneline`, shortlogs and hosting UIs that truncate them.",
+        good: "Add retry to the upload client",
+        bad: "Add a retry loop with exponential backoff to the upload client",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-max-words",
+        description: "The subject must not have more words than `subject_max_words`, verb included.",
+        rationale: "A word limit keeps subjects to a single, scannable idea.",
+        good: "Add upload retries",
+        bad: "Add retries for uploads that fail on flaky networks",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "standard-verb",
+        description: "The subject must start with one of the configured standard verbs.",
+        rationale: "A fixed vocabulary makes history easy to scan and filter by kind of change.",
+        good: "Fix crash when the config file is empty",
+        bad: "Crash fixed when the config file is empty",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "conventional-header",
+        description: "With `convention = \"conventional\"`, the subject must be a Conventional Commits header: a type from `conventional_types`, an optional `(scope)`, an optional `!`, then `: ` and a description.",
+        rationale: "Release and changelog tools parse the header, so a malformed one is silently left out.",
+        good: "feat(auth)!: drop the legacy login endpoint",
+        bad: "feat(auth) drop the legacy login endpoint",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-description",
+        description: "The subject must say what the verb applies to, not be the verb alone.",
+        rationale: "A bare \"Fix\" or \"Add\" gives no hint of what changed.",
+        good: "Fix crash on empty config",
+        bad: "Fix",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-full-stop",
+        description: "The subject line must not end with a full stop.",
+        rationale: "The subject is a title, and the full stop wastes one of its few characters.",
+        good: "Add retry to the upload client",
+        bad: "Add retry to the upload client.",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-capitalised",
+        description: "The first letter of the subject must not be lower case, unless the subject starts with a number. With `strict_capitalisation`, the very first character must be a capital.",
+        rationale: "Consistent capitalisation keeps the log tidy and matches git's own messages.",
+        good: "Add retry to the upload client",
+        bad: "add retry to the upload client",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "blank-line-after-subject",
+        description: "A blank line must separate the subject from the body.",
+        rationale: "Git and its tools treat everything up to the first blank line as the subject.",
+        good: "Add retry to the upload client\n\nUploads fail on flaky networks.",
+        bad: "Add retry to the upload client\nUploads fail on flaky networks.",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "body-line-length",
+        description: "Body lines must not exceed the configured length, 72 characters by default.",
+        rationale: "Git indents the body in many views, and 72 columns keeps it readable in an 80 column terminal.",
+        good: "Uploads fail on flaky networks, so retry them\nwith a backoff.",
+        bad: "Uploads fail on flaky networks, so retry them with a backoff of up to a minute.",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "body-max-lines",
+        description: "The body should not have more non-blank lines than `max_body_lines`.",
+        rationale: "Very long bodies often mean the commit does too much, or the detail belongs in docs.",
+        good: "Uploads fail on flaky networks, so retry them.",
+        bad: "A body that runs on for dozens of lines.",
+        severity: Severity::Warning,
+    },
+    RuleInfo {
+        id: "control-character",
+        description: "The message must not contain invisible control characters other than tabs.",
+        rationale: "Stray carriage returns or escape codes corrupt terminal output and are never intended.",
+        good: "Add retry to the upload client",
+        bad: "Add retry to the upload client\u{7}",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "trailing-whitespace",
+        description: "No line may end in spaces or tabs.",
+        rationale: "Trailing whitespace is invisible in most editors and shows up as noise in diffs of rewritten messages.",
+        good: "Add retry to the upload client",
+        bad: "Add retry to the upload client  ",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "no-tabs",
+        description: "Lines must not contain tab characters.",
+        rationale: "Tabs render at different widths in terminals and web views, so wrapped bodies lose their alignment.",
+        good: "    let retries = 3;",
+        bad: "\tlet retries = 3;",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "final-newline",
+        description: "A message file must end with exactly one newline.",
+        rationale: "Git writes message files that way, and some tools misread a missing or doubled final newline.",
+        good: "Add retry to the upload client\n",
+        bad: "Add retry to the upload client",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "unedited-section",
+        description: "Template sections must not be left exactly as generated, when `require_edited_sections` is set.",
+        rationale: "An untouched placeholder means a section was skipped rather than filled in.",
+        good: "# Testing Instructions [Optional]\n1. Upload a file with the network throttled",
+        bad: "# Testing Instructions [Optional]\n1. Steps to test",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "subject-mood",
+        description: "The subject must start in the imperative, not a past tense, gerund or third-person form, when `check_subject_mood` is set.",
+        rationale: "A subject reads as what the commit does when applied: \"Fix login\", not \"Fixed login\".",
+        good: "Fix login redirect",
+        bad: "Fixed login redirect",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "description-mood",
+        description: "The word after the verb must not be a gerund or past tense form, when `check_description_mood` is set.",
+        rationale: "The whole subject should read as a command, like the verb that starts it.",
+        good: "Add fix for the parser",
+        bad: "Add fixing the parser",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "single-change",
+        description: "The subject should not join two actions, as in \"Add X and fix Y\", when `check_single_change` is set.",
+        rationale: "A commit that does two things is harder to review, revert and bisect than two commits.",
+        good: "Add login form",
+        bad: "Add login and fix logout",
+        severity: Severity::Warning,
+    },
+    RuleInfo {
+        id: "single-sentence",
+        description: "The subject must be one sentence, with no full stop, `!` or `?` partway through, when `check_single_sentence` is set.",
+        rationale: "The subject is a one line summary; anything after the first sentence belongs in the body.",
+        good: "Add login page",
+        bad: "Add login page. Also refactor auth",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "required-footer",
+        description: "Commits starting with a verb listed in `required_footers` must carry those footers.",
+        rationale: "Some kinds of change, such as fixes, should always link back to the issue they address.",
+        good: "Fix crash on empty config\n\nFixes: #12",
+        bad: "Fix crash on empty config",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "sign-off",
+        description: "With `require_sign_off` set, the final paragraph must hold a `Signed-off-by: Name <email>` trailer, and every sign-off there must have that shape.",
+        rationale: "Projects under the Developer Certificate of Origin only accept commits their authors have signed off.",
+        good: "Fix crash on empty config\n\nSigned-off-by: Jo Bloggs <jo@example.com>",
+        bad: "Fix crash on empty config\n\nSigned-off-by: Jo Bloggs",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "reference-keyword",
+        description: "Issue references such as `Closes #12` must use one of `reference_keywords`, when it is set.",
+        rationale: "Hosts only act on the keywords they know, and one vocabulary keeps references searchable.",
+        good: "Closes #12",
+        bad: "Fixes #12",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "footer-grammar",
+        description: "Lines in the footer paragraph must be `token: value` or `token #value`, when `check_footer_grammar` is set.",
+        rationale: "Conventional Commits tooling only understands footers written in this grammar.",
+        good: "Reviewed-by: Alex\nRefs #12",
+        bad: "Reviewed by: Alex",
+        severity: Severity::Error,
+    },
+    RuleInfo {
+        id: "duplicate-subject",
+        description: "The subject should not repeat one of the last `duplicate_subject_lookback` commits' subjects.",
+        rationale: "Identical subjects make history ambiguous, and usually mean a message was copied without editing.",
+        good: "Fix retry delay on upload timeouts",
+        bad: "Fix upload bug",
+        severity: Severity::Warning,
+    },
+    RuleInfo {
+        id: "category-verb",
+        description: "When every staged file is of one kind, the verb should be the one set for it in `category_verbs`.",
+        rationale: "A docs-only change described as a fix is usually a mislabelled commit.",
+        good: "Document the upload retry settings",
+        bad: "Fix the upload retry settings",
+        severity: Severity::Warning,
+    },
+];
+
+/// Whether a violation fails verification or is only reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn label(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A violation as reported: its rule, the severity in effect and the
+/// display text, custom messages applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule_id: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    /// Whether this finding fails verification.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "{}", self.message),
+            Severity::Warning => write!(f, "warning: {}", self.message),
+        }
+    }
+}
+
+/// Looks up a rule in [`RULES`] by its ID.
+pub fn rule_info(id: &str) -> Option<&'static RuleInfo> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// Formats the catalog entry for a rule, as shown by `scripture explain`.
+pub fn explain_rule(rule: &RuleInfo) -> String {
+    let example = |text: &str| {
+        if text.is_empty() {
+            "    (empty)".to_string()
+        } else {
+            text.lines()
+                .map(|line| match line {
+                    "" => String::new(),
+                    line => format!("    {}", line),
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    };
+
+    format!(
+        "{} ({})\n\n{}\n\nWhy: {}\n\nGood:\n{}\n\nBad:\n{}",
+        rule.id,
+        rule.severity.label(),
+        rule.description,
+        rule.rationale,
+        example(rule.good),
+        example(rule.bad)
+    )
+}
+
+/// Describes how a verb was chosen, for `scripture explain` without a rule:
+/// the reason, each indicator word behind it with the line it was found in,
+/// and the runners-up.
+pub fn explain_verb(choice: &VerbChoice) -> String {
+    let mut text = match choice.reason {
+        VerbReason::Given => format!("{}, as chosen", choice.verb),
+        VerbReason::AllAdded => format!("{}, because every file is newly added", choice.verb),
+        VerbReason::AllDeleted => format!("{}, because every file is deleted", choice.verb),
+        VerbReason::Indicators => format!(
+            "{}, from the indicator words in the added lines:",
+            choice.verb
+        ),
+        VerbReason::Default => format!(
+            "{}, the default, as no indicator words matched the added lines",
+            choice.verb
+        ),
+    };
+    for m in &choice.matches {
+        let times = if m.count == 1 {
+            String::new()
+        } else {
+            format!(", {} times", m.count)
+        };
+        text.push_str(&format!(
+            "\n- \"{}\" ({}) in {}{}: {}",
+            m.word, m.indicator, m.file, times, m.line
+        ));
+    }
+
+    let others: Vec<String> = choice
+        .candidates
+        .iter()
+        .filter(|candidate| candidate.verb != choice.verb)
+        .map(|candidate| {
+            let noun = if candidate.matches == 1 {
+                "match"
+            } else {
+                "matches"
+            };
+            format!("{} ({} {})", candidate.verb, candidate.matches, noun)
+        })
+        .collect();
+    if !others.is_empty() {
+        text.push_str(&format!("\n\nAlso matched: {}", others.join(", ")));
+    }
+    text
+}
+
+impl Violation {
+    /// The ID of the rule this violation breaks, e.g. `subject-length`.
+    pub fn rule_id(&self) -> &'static str {
+        match self {
+            Violation::EmptyMessage => "empty-message",
+            Violation::SubjectTooLong { .. } => "subject-length",
+            Violation::UnknownVerb { .. } => "standard-verb",
+            Violation::MissingDescription { .. } => "subject-description",
+            Violation::SubjectFullStop => "subject-full-stop",
+            Violation::SubjectNotCapitalised => "subject-capitalised",
+            Violation::MissingBlankLine => "blank-line-after-subject",
+            Violation::BodyLineTooLong { .. } => "body-line-length",
+            Violation::UneditedSection { .. } => "unedited-section",
+            Violation::NonImperativeDescription { .. } => "description-mood",
+            Violation::NonImperativeSubject { .. } => "subject-mood",
+            Violation::BodyTooLong { .. } => "body-max-lines",
+            Violation::MissingFooter { .. } => "required-footer",
+            Violation::MissingSignOff | Violation::MalformedSignOff { .. } => "sign-off",
+            Violation::SubjectTooManyWords { .. } => "subject-max-words",
+            Violation::ControlCharacter { .. } => "control-character",
+            Violation::TrailingWhitespace { .. } => "trailing-whitespace",
+            Violation::TabCharacter { .. } => "no-tabs",
+            Violation::CategoryVerbMismatch { .. } => "category-verb",
+            Violation::MalformedFooter { .. } => "footer-grammar",
+            Violation::FinalNewline { .. } => "final-newline",
+            Violation::MultipleChanges { .. } => "single-change",
+            Violation::MultipleSentences { .. } => "single-sentence",
+            Violation::DuplicateSubject { .. } => "duplicate-subject",
+            Violation::UnknownReferenceKeyword { .. } => "reference-keyword",
+            Violation::MalformedHeader { .. } => "conventional-header",
+        }
+    }
+
+    /// The default severity of this violation's rule, before any
+    /// `rule_severities` override.
+    pub fn severity(&self) -> Severity {
+        rule_info(self.rule_id()).map_or(Severity::Error, |rule| rule.severity)
+    }
+
+    fn placeholders(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Violation::SubjectTooLong { len, max } => {
+                vec![("len", len.to_string()), ("max", max.to_string())]
+            }
+            Violation::UnknownVerb {
+                verb,
+                allowed,
+                suggestion,
+            } => {
+                let names: Vec<&str> = allowed.iter().map(|(name, _)| name.as_str()).collect();
+                vec![
+                    ("verb", verb.clone()),
+                    ("allowed", names.join(", ")),
+                    ("suggestion", suggestion.clone().unwrap_or_default()),
+                ]
+            }
+            Violation::BodyLineTooLong { line, len, max } => vec![
+                ("line", line.to_string()),
+                ("len", len.to_string()),
+                ("max", max.to_string()),
+            ],
+            Violation::UneditedSection { section } => vec![("section", section.clone())],
+            Violation::MissingDescription { verb } => vec![("verb", verb.clone())],
+            Violation::NonImperativeDescription { word, suggestion } => {
+                vec![("word", word.clone()), ("suggestion", suggestion.clone())]
+            }
+            Violation::NonImperativeSubject { word, suggestion } => vec![
+                ("word", word.clone()),
+                ("suggestion", suggestion.clone().unwrap_or_default()),
+            ],
+            Violation::BodyTooLong { count, max }
+            | Violation::SubjectTooManyWords { count, max } => {
+                vec![("count", count.to_string()), ("max", max.to_string())]
+            }
+            Violation::MissingFooter { verb, footer } => {
+                vec![("verb", verb.clone()), ("footer", footer.clone())]
+            }
+            Violation::MalformedSignOff { value } => vec![("value", value.clone())],
+            Violation::ControlCharacter { line, column, code } => vec![
+                ("line", line.to_string()),
+                ("column", column.to_string()),
+                ("code", format!("U+{:04X}", code)),
+            ],
+            Violation::TrailingWhitespace { line } => vec![("line", line.to_string())],
+            Violation::TabCharacter { line, column } => {
+                vec![("line", line.to_string()), ("column", column.to_string())]
+            }
+            Violation::MalformedFooter { line, text } => {
+                vec![("line", line.to_string()), ("text", text.clone())]
+            }
+            Violation::FinalNewline { count } => vec![("count", count.to_string())],
+            Violation::DuplicateSubject { subject } => vec![("subject", subject.clone())],
+            Violation::MultipleSentences { rest } => vec![("rest", rest.clone())],
+            Violation::MalformedHeader { problem } => vec![("problem", problem.clone())],
+            Violation::UnknownReferenceKeyword {
+                line,
+                keyword,
+                allowed,
+            } => vec![
+                ("line", line.to_string()),
+                ("keyword", keyword.clone()),
+                ("allowed", allowed.join(", ")),
+            ],
+            Violation::MultipleChanges {
+                conjunction,
+                second,
+            } => vec![
+                ("conjunction", conjunction.clone()),
+                ("second", second.clone()),
+            ],
+            Violation::CategoryVerbMismatch {
+                verb,
+                category,
+                expected,
+            } => vec![
+                ("verb", verb.clone()),
+                ("category", category.clone()),
+                ("expected", expected.clone()),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    // Renders the violation using the configured message for its rule, if
+    // any, substituting `{name}` placeholders with the violation's values.
+    fn render(&self, custom_messages: &BTreeMap<String, String>) -> String {
+        match custom_messages.get(self.rule_id()) {
+            Some(template) => self
+                .placeholders()
+                .into_iter()
+                .fold(template.clone(), |message, (name, value)| {
+                    message.replace(&format!("{{{}}}", name), &value)
+                }),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::EmptyMessage => write!(f, "Empty commit message"),
+            Violation::SubjectTooLong { max, .. } => {
+                write!(f, "Subject line exceeds {} characters", max)
+            }
+            Violation::UnknownVerb {
+                verb,
+                suggestion: Some(suggestion),
+                ..
+            } => write!(
+                f,
+                "Subject must start with a standard verb, not '{}'; did you mean '{}'?",
+                verb, suggestion
+            ),
+            Violation::UnknownVerb { allowed, .. } => {
+                write!(f, "Subject must start with standard verb:")?;
+                let width = allowed
+                    .iter()
+                    .map(|(name, _)| name.len())
+                    .max()
+                    .unwrap_or(0);
+                for (name, description) in allowed {
+                    write!(f, "\n    {:<width$}  {}", name, description, width = width)?;
+                }
+                Ok(())
+            }
+            Violation::MissingDescription { verb } => {
+                write!(f, "Subject has no description after the verb '{}'", verb)
+            }
+            Violation::SubjectFullStop => write!(f, "Subject line ends with a full stop"),
+            Violation::SubjectNotCapitalised => write!(f, "Subject line not capitalised"),
+            Violation::MissingBlankLine => write!(f, "No blank line between subject and body"),
+            Violation::BodyLineTooLong { line, max, .. } => {
+                write!(f, "Line {} exceeds {} characters", line, max)
+            }
+            Violation::UneditedSection { section } => {
+                write!(f, "Section '{}' is unchanged from the template", section)
+            }
+            Violation::NonImperativeDescription { word, suggestion } => write!(
+                f,
+                "Description should start in the imperative: use '{}' instead of '{}'",
+                suggestion, word
+            ),
+            Violation::NonImperativeSubject {
+                word,
+                suggestion: Some(suggestion),
+            } => write!(
+                f,
+                "Subject should start in the imperative: use '{}' instead of '{}'",
+                suggestion, word
+            ),
+            Violation::NonImperativeSubject {
+                word,
+                suggestion: None,
+            } => write!(f, "Subject should start in the imperative, not '{}'", word),
+            Violation::BodyTooLong { count, max } => {
+                write!(
+                    f,
+                    "Body has {} lines, more than the limit of {}",
+                    count, max
+                )
+            }
+            Violation::MissingFooter { verb, footer } => {
+                write!(f, "'{}' commits require a '{}:' footer", verb, footer)
+            }
+            Violation::MissingSignOff => write!(
+                f,
+                "Missing a 'Signed-off-by:' trailer, add one with 'git commit -s'"
+            ),
+            Violation::MalformedSignOff { value } => write!(
+                f,
+                "'Signed-off-by: {}' should read 'Signed-off-by: Name <email>'",
+                value
+            ),
+            Violation::ControlCharacter { line, column, code } => write!(
+                f,
+                "Line {} column {} contains control character U+{:04X}",
+                line, column, code
+            ),
+            Violation::TrailingWhitespace { line } => {
+                write!(f, "Line {} ends in trailing whitespace", line)
+            }
+            Violation::TabCharacter { line, column } => {
+                write!(f, "Line {} column {} contains a tab", line, column)
+            }
+            Violation::SubjectTooManyWords { count, max } => {
+                write!(
+                    f,
+                    "Subject has {} words, more than the limit of {}",
+                    count, max
+                )
+            }
+            Violation::CategoryVerbMismatch {
+                verb,
+                category,
+                expected,
+            } => write!(
+                f,
+                "Subject starts with '{}' but only {} files are staged; expected '{}'",
+                verb, category, expected
+            ),
+            Violation::MultipleChanges {
+                conjunction,
+                second,
+            } => write!(
+                f,
+                "Subject joins a second change with '{} {}'; consider splitting the commit",
+                conjunction, second
+            ),
+            Violation::MalformedHeader { problem } => {
+                write!(f, "Conventional Commits header is malformed: {}", problem)
+            }
+            Violation::MultipleSentences { rest } => write!(
+                f,
+                "Subject holds more than one sentence; move '{}' to the body",
+                rest
+            ),
+            Violation::UnknownReferenceKeyword {
+                line,
+                keyword,
+                allowed,
+            } => write!(
+                f,
+                "Line {} references an issue with '{}'; use one of: {}",
+                line,
+                keyword,
+                allowed.join(", ")
+            ),
+            Violation::DuplicateSubject { subject } => {
+                write!(f, "A recent commit already has the subject '{}'", subject)
+            }
+            Violation::FinalNewline { count: 0 } => {
+                write!(f, "Message does not end with a newline")
+            }
+            Violation::FinalNewline { count } => {
+                write!(f, "Message ends with {} newlines instead of one", count)
+            }
+            Violation::MalformedFooter { line, text } => write!(
+                f,
+                "Line {} is not a valid footer, expected 'token: value' or 'token #value': {}",
+                line, text
+            ),
+        }
+    }
+}
+
+// Common gerund and past-tense forms mapped to their imperative base form.
+const NON_IMPERATIVE_FORMS: &[(&str, &str)] = &[
+    ("adding", "add"),
+    ("added", "add"),
+    ("allowing", "allow"),
+    ("allowed", "allow"),
+    ("changing", "change"),
+    ("changed", "change"),
+    ("cleaning", "clean"),
+    ("cleaned", "clean"),
+    ("creating", "create"),
+    ("created", "create"),
+    ("deleting", "delete"),
+    ("deleted", "delete"),
+    ("fixing", "fix"),
+    ("fixed", "fix"),
+    ("handling", "handle"),
+    ("handled", "handle"),
+    ("implementing", "implement"),
+    ("implemented", "implement"),
+    ("improving", "improve"),
+    ("improved", "improve"),
+    ("making", "make"),
+    ("made", "make"),
+    ("moving", "move"),
+    ("moved", "move"),
+    ("refactoring", "refactor"),
+    ("refactored", "refactor"),
+    ("removing", "remove"),
+    ("removed", "remove"),
+    ("renaming", "rename"),
+    ("renamed", "rename"),
+    ("supporting", "support"),
+    ("supported", "support"),
+    ("updating", "update"),
+    ("updated", "update"),
+    ("using", "use"),
+    ("used", "use"),
+    ("writing", "write"),
+    ("wrote", "write"),
+];
+
+// Removes a leading `:shortcode:` or emoji token and the space after it.
+fn strip_gitmoji(subject: &str) -> &str {
+    let Some((token, rest)) = subject.split_once(' ') else {
+        return subject;
+    };
+    let shortcode = token.len() > 2
+        && token.starts_with(':')
+        && token.ends_with(':')
+        && token[1..token.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+'));
+    if shortcode || token.chars().all(is_emoji) {
+        rest.trim_start()
+    } else {
+        subject
+    }
+}
+
+// Pictographs and the joiners and modifiers that combine them. This covers
+// every gitmoji without pulling in the full Unicode emoji tables.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF
+            | 0x2300..=0x23FF
+            | 0x2600..=0x27BF
+            | 0x2B00..=0x2BFF
+            | 0x2190..=0x21FF
+            | 0x3030
+            | 0x303D
+            | 0x200D
+            | 0xFE0F
+    )
+}
+
+// The imperative a conjugated word stands for, if it looks conjugated at all:
+// "Fixed", "Fixing" and "Fixes" all give `Some(Some("Fix"))`. A standard verb
+// is preferred; a bare "-s" is only taken as conjugated when it leaves one,
+// while other "-ed" and "-ing" words, bar "-eed" as in "Speed", are flagged
+// without a suggestion.
+fn imperative_form(word: &str, verbs: &BTreeMap<String, String>) -> Option<Option<String>> {
+    let lower = word.to_lowercase();
+    let known = |stem: &str| {
+        verbs
+            .keys()
+            .find(|verb| verb.to_lowercase() == stem)
+            .cloned()
+    };
+    let capitalised = |base: &str| {
+        let mut chars = base.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+    };
+
+    if let Some((_, base)) = NON_IMPERATIVE_FORMS.iter().find(|(form, _)| *form == lower) {
+        return Some(known(base).or_else(|| capitalised(base)));
+    }
+
+    for suffix in ["ing", "ies", "ed", "es", "s"] {
+        let Some(stem) = lower.strip_suffix(suffix) else {
+            continue;
+        };
+        if stem.len() < 2 {
+            continue;
+        }
+        let mut candidates = match suffix {
+            "ies" => vec![format!("{}y", stem)],
+            _ => vec![stem.to_string(), format!("{}e", stem)],
+        };
+        // A doubled final consonant, as in "stopped" or "cutting"
+        let bytes = stem.as_bytes();
+        if stem.is_ascii() && bytes[bytes.len() - 1] == bytes[bytes.len() - 2] {
+            candidates.push(stem[..stem.len() - 1].to_string());
+        }
+        if let Some(verb) = candidates.iter().find_map(|candidate| known(candidate)) {
+            return Some(Some(verb));
+        }
+    }
+
+    let conjugated = (lower.ends_with("ed") && !lower.ends_with("eed")) || lower.ends_with("ing");
+    (lower.len() > 4 && conjugated).then_some(None)
+}
+
+/// The config file read from the current directory by default.
+pub const CONFIG_FILE: &str = ".scripture.toml";
+/// The ignore file read from the current directory by default.
+pub const IGNORE_FILE: &str = ".scriptureignore";
+const ENV_PREFIX: &str = "SCRIPTURE_";
+/// Names a central verb policy file that replaces the default verbs.
+pub const VERB_POLICY_ENV: &str = "SCRIPTURE_VERB_POLICY";
+/// The config format version written by `migrate-config`.
+pub const CONFIG_VERSION: u32 = 1;
+
+impl Default for Config {
+    fn default() -> Self {
+        let standard_verbs = [
+            ("Add", "Create a capability, e.g. feature, test, dependency"),
+            ("Cut", "Remove a capability, e.g. feature, test, dependency"),
+            ("Fix", "Fix an issue, e.g. bug, typo, error, misstatement"),
+            ("Bump", "Increase the version of something, e.g. dependency"),
+            (
+                "Make",
+                "Change the build process, tooling or infrastructure",
+            ),
+            ("Start", "Begin doing something, e.g. create a feature flag"),
+            ("Stop", "End doing something, e.g. remove a feature flag"),
+            ("Refactor", "Change code without changing its behaviour"),
+            ("Reformat", "Refactor of formatting, e.g. omit whitespace"),
+            ("Optimise", "Refactor of performance, e.g. speed up code"),
+            ("Optimize", "Refactor of performance, e.g. speed up code"),
+            ("Document", "Refactor of documentation, e.g. help files"),
+            ("Test", "Add or update tests, e.g. unit, integration"),
+        ]
+        .iter()
+        .map(|(verb, description)| (verb.to_string(), description.to_string()))
+        .collect();
+
+        let indicator_words: &[(&str, &str, &[&str])] = &[
+            ("add", "Add", &["new", "create", "implement", "introduce"]),
+            ("cut", "Cut", &["remove", "delete", "drop"]),
+            ("fix", "Fix", &["fix", "bug", "issue"]),
+            ("bump", "Bump", &["bump", "upgrade", "version"]),
+            (
+                "make",
+                "Make",
+                &["build", "makefile", "dockerfile", "pipeline"],
+            ),
+            ("start", "Start", &["enable", "start"]),
+            ("stop", "Stop", &["disable", "stop"]),
+            (
+                "refactor",
+                "Refactor",
+                &["refactor", "rename", "extract", "simplify"],
+            ),
+            (
+                "reformat",
+                "Reformat",
+                &["reformat", "whitespace", "indent", "rustfmt"],
+            ),
+            (
+                "optimise",
+                "Optimise",
+                &["optimise", "optimize", "performance", "faster"],
+            ),
+            (
+                "document",
+                "Document",
+                &["readme", "documentation", "docs", "///"],
+            ),
+            ("test", "Test", &["#[test]", "assert", "mock", "fixture"]),
+        ];
+        let indicators = indicator_words
+            .iter()
+            .map(|(key, _, words)| {
+                let words = words.iter().map(|word| word.to_string()).collect();
+                (key.to_string(), words)
+            })
+            .collect();
+        let verb_mapping = indicator_words
+            .iter()
+            .map(|(key, verb, _)| (key.to_string(), verb.to_string()))
+            .collect();
+
+        Config {
+            version: CONFIG_VERSION,
+            standard_verbs,
+            indicators,
+            verb_mapping,
+            message_template: MessageTemplate::default(),
+            custom_messages: BTreeMap::new(),
+            rule_severities: BTreeMap::new(),
+            test_advisory: false,
+            source_file_patterns: [
+                "*.rs", "*.py", "*.js", "*.ts", "*.go", "*.java", "*.c", "*.cpp", "*.rb",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            test_file_patterns: [
+                "tests/**",
+                "**/tests/**",
+                "**/*_test.*",
+                "**/test_*",
+                "**/*.test.*",
+                "**/*.spec.*",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            require_edited_sections: false,
+            fail_fast: false,
+            check_description_mood: false,
+            check_subject_mood: false,
+            max_body_lines: None,
+            required_footers: BTreeMap::new(),
+            require_sign_off: false,
+            subject_max_words: None,
+            standalone_subjects: Vec::new(),
+            doc_file_patterns: ["*.md", "*.rst", "*.adoc", "docs/**", "doc/**"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            check_category_verb: false,
+            check_footer_grammar: false,
+            category_verbs: [("docs", "Document"), ("tests", "Test")]
+                .iter()
+                .map(|(category, verb)| (category.to_string(), verb.to_string()))
+                .collect(),
+            subject_max_length: 50,
+            body_max_line_length: 72,
+            history_count: 3,
+            binary_size_limit: 1024 * 1024,
+            strip_prefixes: Vec::new(),
+            count_prefixes_in_length: true,
+            require_blank_after_subject: true,
+            include_excerpt: false,
+            excerpt_lines: 5,
+            require_final_newline: true,
+            check_trailing_whitespace: true,
+            check_tabs: false,
+            allow_merge_commits: true,
+            git_timeout_secs: 30,
+            check_single_change: false,
+            check_single_sentence: false,
+            duplicate_subject_lookback: None,
+            body_wrap: BodyWrap::Hard,
+            wrap_generated_body: true,
+            reference_keywords: Vec::new(),
+            default_branch: None,
+            convention: Convention::Imperative,
+            strict_capitalisation: false,
+            conventional_types: [
+                "feat", "fix", "build", "chore", "ci", "docs", "style", "refactor", "perf", "test",
+                "revert",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            branch_ticket_pattern: r"(?:^|/)([A-Z][A-Z0-9]*-[0-9]+|[0-9]+)(?:[-_/]|$)".to_string(),
+            gitmoji: false,
+            group_depth: None,
+            changes_per_file: 3,
+            max_listed_files: None,
+            changelog_sections: [
+                ("Add", "Added"),
+                ("Start", "Added"),
+                ("Cut", "Removed"),
+                ("Stop", "Removed"),
+                ("Fix", "Fixed"),
+            ]
+            .iter()
+            .map(|(verb, section)| (verb.to_string(), section.to_string()))
+            .collect(),
+            gitmoji_map: [
+                ("Add", ":sparkles:"),
+                ("Cut", ":fire:"),
+                ("Fix", ":bug:"),
+                ("Bump", ":arrow_up:"),
+                ("Make", ":wrench:"),
+                ("Start", ":triangular_flag_on_post:"),
+                ("Stop", ":triangular_flag_on_post:"),
+                ("Refactor", ":recycle:"),
+                ("Reformat", ":art:"),
+                ("Optimise", ":zap:"),
+                ("Optimize", ":zap:"),
+                ("Document", ":memo:"),
+                ("Test", ":white_check_mark:"),
+            ]
+            .iter()
+            .map(|(verb, emoji)| (verb.to_string(), emoji.to_string()))
+            .collect(),
+        }
+    }
+}
+
+impl Default for MessageTemplate {
+    fn default() -> Self {
+        MessageTemplate {
+            references_section: "# References [Required]\n# Link to related tickets, docs, or discussions\nCloses #\nRelates to #\nSee also:".to_string(),
+            testing_section: "# Testing Instructions [Optional]\n# Describe how to test these changes\n1. Steps to test\n2. Expected outcomes\n3. Edge cases to verify".to_string(),
+            dependencies_section: "# Dependencies [Optional]\n# List any prerequisite changes or dependencies\n- [ ] Database migrations\n- [ ] Configuration updates\n- [ ] External service changes".to_string(),
+            changes_section: "# Changes Overview [Required]\n# Briefly describe the purpose of these changes".to_string(),
+            breaking_section: "# Breaking Changes [Required if any]\n# List any backward-incompatible changes and migration steps".to_string(),
+            references_section_file: None,
+            testing_section_file: None,
+            dependencies_section_file: None,
+            changes_section_file: None,
+            breaking_section_file: None,
+        }
+    }
+}
+
+impl MessageTemplate {
+    // Replaces each section that names a `*_file` with that file's
+    // contents. Relative paths are resolved against `base`, the directory
+    // holding the config.
+    fn load_section_files(&mut self, base: &Path) -> Result<(), String> {
+        let sections = [
+            (&self.references_section_file, &mut self.references_section),
+            (&self.testing_section_file, &mut self.testing_section),
+            (
+                &self.dependencies_section_file,
+                &mut self.dependencies_section,
+            ),
+            (&self.changes_section_file, &mut self.changes_section),
+            (&self.breaking_section_file, &mut self.breaking_section),
+        ];
+
+        for (file, section) in sections {
+            let Some(file) = file else {
+                continue;
+            };
+            let path = base.join(file);
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?;
+            *section = contents.trim_end_matches('\n').to_string();
+        }
+
+        Ok(())
+    }
+
+    /// Every section, in the order they appear in a generated message.
+    pub fn sections(&self) -> [&str; 5] {
+        [
+            &self.references_section,
+            &self.changes_section,
+            &self.breaking_section,
+            &self.testing_section,
+            &self.dependencies_section,
+        ]
+    }
+}
+
+/// The first line of a template section is its heading, e.g.
+/// `# Testing Instructions [Optional]`, which names it "Testing Instructions".
+pub fn section_heading(section: &str) -> &str {
+    section.lines().next().unwrap_or("").trim_end()
+}
+
+fn section_name(section: &str) -> &str {
+    let heading = section_heading(section).trim_start_matches('#').trim();
+    match heading.find('[') {
+        Some(pos) => heading[..pos].trim_end(),
+        None => heading,
+    }
+}
+
+/// Splits a message on the given section headings, returning the text under
+/// each heading that appears (heading line excluded). Anything before the
+/// first heading, such as the subject, is not included.
+pub fn split_sections<'a>(message: &str, headings: &[&'a str]) -> Vec<(&'a str, String)> {
+    let mut sections: Vec<(&'a str, String)> = Vec::new();
+
+    for line in message.lines() {
+        if let Some(heading) = headings.iter().find(|h| **h == line.trim_end()) {
+            sections.push((heading, String::new()));
+        } else if let Some((_, body)) = sections.last_mut() {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+
+    sections
+}
+
+impl Config {
+    /// Reads the config at `path`, TOML or JSON by extension, falling back to
+    /// the defaults when the file doesn't exist.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+        let parsed = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        let mut config: Config =
+            parsed.map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+
+        if config.version > CONFIG_VERSION {
+            return Err(format!(
+                "Config {} has version {}, but this scripture only understands up to {}",
+                path.display(),
+                config.version,
+                CONFIG_VERSION
+            ));
+        }
+
+        config
+            .check_limits()
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.message_template.load_section_files(base)?;
+        Ok(config)
+    }
+
+    // Settings that parse but leave no room for a valid subject.
+    fn check_limits(&self) -> Result<(), String> {
+        match self.subject_max_words {
+            Some(max) if max < 2 => Err(format!(
+                "subject_max_words is {}, but a subject needs a verb and at least one more word",
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Overrides top-level settings from `SCRIPTURE_<SETTING>` variables, e.g.
+    /// `SCRIPTURE_MAX_BODY_LINES=20`. Values are read as TOML, so lists and
+    /// booleans work, and anything that doesn't parse is taken as a string.
+    pub fn apply_env(self, vars: impl Iterator<Item = (String, String)>) -> Result<Self, String> {
+        // JSON keeps unset optional settings as nulls, so they can be overridden too
+        let mut document = match serde_json::to_value(&self) {
+            Ok(serde_json::Value::Object(document)) => document,
+            Ok(_) => unreachable!("config serialises to an object"),
+            Err(e) => return Err(format!("Failed to serialise config: {}", e)),
+        };
+        let mut changed = false;
+
+        for (name, raw) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let key = key.to_lowercase();
+            if !document.contains_key(&key) {
+                continue;
+            }
+
+            let value = toml::from_str::<toml::Table>(&format!("value = {}", raw))
+                .ok()
+                .and_then(|mut table| table.remove("value"))
+                .and_then(|value| serde_json::to_value(value).ok())
+                .unwrap_or(serde_json::Value::String(raw));
+            document.insert(key, value);
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(self);
+        }
+
+        let config: Config = serde_json::from_value(serde_json::Value::Object(document))
+            .map_err(|e| format!("Invalid {}* environment override: {}", ENV_PREFIX, e))?;
+        config
+            .check_limits()
+            .map_err(|e| format!("Invalid {}* environment override: {}", ENV_PREFIX, e))?;
+        Ok(config)
+    }
+
+    /// Replaces the verb set with the policy's. Verbs the repository added on
+    /// top of the defaults are kept unless the policy is locked, in which case
+    /// they are returned so the caller can report them.
+    pub fn apply_verb_policy(mut self, policy: VerbPolicy) -> (Self, Vec<String>) {
+        let defaults = Config::default().standard_verbs;
+        let added: Vec<(String, String)> = std::mem::take(&mut self.standard_verbs)
+            .into_iter()
+            .filter(|(verb, _)| {
+                !defaults.contains_key(verb) && !policy.standard_verbs.contains_key(verb)
+            })
+            .collect();
+
+        self.standard_verbs = policy.standard_verbs;
+        if policy.locked {
+            return (self, added.into_iter().map(|(verb, _)| verb).collect());
+        }
+
+        self.standard_verbs.extend(added);
+        (self, Vec::new())
+    }
+
+    fn migrate(mut document: toml::Table) -> Result<Self, String> {
+        let version = match document.get("version") {
+            Some(value) => value
+                .as_integer()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("Invalid config version: {}", value))?,
+            // Configs written before versioning was introduced
+            None => 0,
+        };
+
+        if version > CONFIG_VERSION {
+            return Err(format!(
+                "Config version {} is newer than this scripture supports ({})",
+                version, CONFIG_VERSION
+            ));
+        }
+
+        // Version 0 configs share the current layout minus the version key.
+        // Fields added since then are filled from the defaults on deserialise.
+        document.insert("version".to_string(), toml::Value::from(CONFIG_VERSION));
+        document
+            .try_into()
+            .map_err(|e| format!("Config does not match the current format: {}", e))
+    }
+}
+
+/// Reads an organisation's verb policy file.
+pub fn load_verb_policy(path: &Path) -> Result<VerbPolicy, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read verb policy {}: {}", path.display(), e))?;
+    toml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse verb policy {}: {}", path.display(), e))
+}
+
+/// Rewrites an older config file in the current format, keeping a backup at
+/// `<path>.bak`, or the first free `<path>.1.bak`, `<path>.2.bak`, ... so an
+/// earlier backup is never overwritten. Returns the backup's path.
+pub fn migrate_config(path: &Path) -> Result<PathBuf, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config {}: {}", path.display(), e))?;
+    let document: toml::Table = toml::from_str(&contents)
+        .map_err(|e| format!("Failed to parse config {}: {}", path.display(), e))?;
+
+    let config = Config::migrate(document)?;
+    let migrated = toml::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialise config: {}", e))?;
+
+    let backup = (0..)
+        .map(|n| {
+            let mut backup = path.as_os_str().to_owned();
+            if n > 0 {
+                backup.push(format!(".{}", n));
+            }
+            backup.push(".bak");
+            PathBuf::from(backup)
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("some numbered name is free");
+
+    fs::copy(path, &backup)
+        .map_err(|e| format!("Failed to back up config to {}: {}", backup.display(), e))?;
+    fs::write(path, migrated)
+        .map_err(|e| format!("Failed to write config {}: {}", path.display(), e))?;
+
+    Ok(backup)
+}
+
+// Marks a commit-msg hook as written by `install-hook`.
+const HOOK_MARKER: &str = "# Installed by scripture install-hook";
+
+/// Writes a commit-msg hook into `hooks_dir` that runs `scripture hook`,
+/// refusing to replace a different hook unless `force` is set. Returns the
+/// hook's path.
+pub fn install_hook(hooks_dir: &Path, force: bool) -> Result<PathBuf, String> {
+    let path = hooks_dir.join("commit-msg");
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARKER) && !force {
+            return Err(format!(
+                "{} already exists; pass --force to replace it",
+                path.display()
+            ));
+        }
+    }
+
+    fs::create_dir_all(hooks_dir)
+        .map_err(|e| format!("Failed to create {}: {}", hooks_dir.display(), e))?;
+    let script = format!("#!/bin/sh\n{}\nexec scripture hook \"$1\"\n", HOOK_MARKER);
+    fs::write(&path, script).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make {} executable: {}", path.display(), e))?;
+    }
+
+    Ok(path)
+}
+
+/// Removes the commit-msg hook from `hooks_dir`, but only if `install_hook`
+/// wrote it. Returns the removed hook's path.
+pub fn uninstall_hook(hooks_dir: &Path) -> Result<PathBuf, String> {
+    let path = hooks_dir.join("commit-msg");
+    let existing = fs::read_to_string(&path)
+        .map_err(|_| format!("No commit-msg hook is installed in {}", hooks_dir.display()))?;
+    if !existing.contains(HOOK_MARKER) {
+        return Err(format!(
+            "{} was not installed by scripture; leaving it in place",
+            path.display()
+        ));
+    }
+
+    fs::remove_file(&path).map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// How git itself begins the subjects it writes for merges, reverts and the
+// commits that `git rebase --autosquash` folds away.
+const GIT_GENERATED_PREFIXES: &[&str] = &[
+    "Merge branch '",
+    "Merge branches '",
+    "Merge remote-tracking branch '",
+    "Merge tag '",
+    "Merge commit '",
+    "Merge pull request #",
+    "fixup! ",
+    "squash! ",
+    "amend! ",
+    "Revert \"",
+];
+
+fn is_git_generated(subject: &str) -> bool {
+    GIT_GENERATED_PREFIXES
+        .iter()
+        .any(|prefix| subject.starts_with(prefix))
+}
+
+// A message already split into the parts the rules inspect. Body lines are
+// numbered from `body_start` when reported.
+struct ParsedMessage<'a> {
+    subject: &'a str,
+    prefix_len: usize,
+    body: &'a str,
+    body_start: usize,
+    missing_blank_line: bool,
+    trailing_newlines: Option<usize>,
+}
+
+type Rule = fn(&CommitMessageVerifier, &ParsedMessage) -> Vec<Violation>;
+
+/// Checks commit messages against the rules enabled in a [`Config`].
+pub struct CommitMessageVerifier {
+    config: Config,
+    file_categories: Vec<FileCategory>,
+    recent_subjects: Vec<String>,
+    comment_char: char,
+}
+
+impl CommitMessageVerifier {
+    /// A verifier for `config`, with no staged files or history to compare against.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            file_categories: Vec::new(),
+            recent_subjects: Vec::new(),
+            comment_char: '#',
+        }
+    }
+
+    /// Categories of the staged files, for rules that compare the message
+    /// against what is actually being committed.
+    pub fn with_file_categories(mut self, categories: Vec<FileCategory>) -> Self {
+        self.file_categories = categories;
+        self
+    }
+
+    // Removes configured tags such as "[skip ci]" from the front of the
+    // subject, returning the rest and how many bytes were removed. A gitmoji,
+    // when enabled, is removed first and not counted.
+    fn strip_prefixes<'a>(&self, subject: &'a str) -> (&'a str, usize) {
+        let subject = if self.config.gitmoji {
+            strip_gitmoji(subject)
+        } else {
+            subject
+        };
+        let mut rest = subject;
+        while let Some(stripped) = self
+            .config
+            .strip_prefixes
+            .iter()
+            .find_map(|prefix| rest.strip_prefix(prefix.as_str()))
+            .filter(|stripped| stripped.len() < rest.len())
+        {
+            rest = stripped.trim_start();
+        }
+
+        let prefix = &subject[..subject.len() - rest.len()];
+        (rest, prefix.chars().count())
+    }
+
+    /// Subjects of recent commits, for the `duplicate-subject` rule.
+    pub fn with_recent_subjects(mut self, subjects: Vec<String>) -> Self {
+        self.recent_subjects = subjects;
+        self
+    }
+
+    /// The character git starts comment lines with in a message file, `#`
+    /// unless `core.commentChar` says otherwise.
+    pub fn with_comment_char(mut self, comment_char: char) -> Self {
+        self.comment_char = comment_char;
+        self
+    }
+
+    /// Verifies a message, returning every problem found, errors and
+    /// warnings alike. It passes when none of them is an error.
+    pub fn verify_message(&self, message: &str) -> Vec<Finding> {
+        self.findings(&self.message_violations(message))
+    }
+
+    /// Every violation in a message, in rule order.
+    pub fn message_violations(&self, message: &str) -> Vec<Violation> {
+        self.text_violations(message, None)
+    }
+
+    // `trailing_newlines` is only known for messages read from a file, where
+    // git expects exactly one.
+    fn text_violations(&self, message: &str, trailing_newlines: Option<usize>) -> Vec<Violation> {
+        if message.lines().next().is_none() {
+            return vec![Violation::EmptyMessage];
+        }
+
+        let (subject, body) = split_message(message);
+        let (subject, prefix_len) = self.strip_prefixes(subject);
+        let lines = message.lines().count();
+        self.check(&ParsedMessage {
+            subject,
+            prefix_len,
+            body,
+            body_start: lines - body.lines().count() + 1,
+            missing_blank_line: message.lines().nth(1).is_some_and(|line| !line.is_empty()),
+            trailing_newlines,
+        })
+    }
+
+    fn structured_violations(&self, message: &StructuredMessage) -> Vec<Violation> {
+        if message.subject.is_empty() && message.body.is_empty() {
+            return vec![Violation::EmptyMessage];
+        }
+
+        let (subject, prefix_len) = self.strip_prefixes(&message.subject);
+        self.check(&ParsedMessage {
+            subject,
+            prefix_len,
+            body: &message.body,
+            body_start: 3,
+            missing_blank_line: false,
+            trailing_newlines: None,
+        })
+    }
+
+    /// The severity of a violation's rule, as set in `rule_severities` or
+    /// else the rule's default.
+    pub fn severity(&self, violation: &Violation) -> Severity {
+        self.config
+            .rule_severities
+            .get(violation.rule_id())
+            .copied()
+            .unwrap_or_else(|| violation.severity())
+    }
+
+    /// `violations` as findings, with the configured severities and
+    /// custom messages applied.
+    pub fn findings(&self, violations: &[Violation]) -> Vec<Finding> {
+        violations
+            .iter()
+            .map(|v| Finding {
+                rule_id: v.rule_id(),
+                severity: self.severity(v),
+                message: v.render(&self.config.custom_messages),
+            })
+            .collect()
+    }
+
+    // Runs every rule in order, stopping after the first error when
+    // `fail_fast` is set. Merge and fixup subjects written by git pass
+    // untouched when `allow_merge_commits` is set.
+    fn check(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if self.config.allow_merge_commits && is_git_generated(message.subject) {
+            return Vec::new();
+        }
+
+        let rules: &[Rule] = &[
+            Self::check_subject_length,
+            Self::check_subject_words,
+            Self::check_standard_verb,
+            Self::check_subject_mood,
+            Self::check_conventional_header,
+            Self::check_description_present,
+            Self::check_full_stop,
+            Self::check_capitalised,
+            Self::check_blank_line,
+            Self::check_body_line_length,
+            Self::check_body_lines,
+            Self::check_control_characters,
+            Self::check_whitespace,
+            Self::check_final_newline,
+            Self::check_edited_sections,
+            Self::check_description_mood,
+            Self::check_single_change,
+            Self::check_single_sentence,
+            Self::check_required_footers,
+            Self::check_sign_off,
+            Self::check_reference_keywords,
+            Self::check_footer_grammar,
+            Self::check_duplicate_subject,
+            Self::check_category_verb,
+        ];
+
+        let mut violations = Vec::new();
+        for rule in rules {
+            violations.extend(rule(self, message));
+            if !self.config.fail_fast {
+                continue;
+            }
+            if let Some(first) = violations
+                .iter()
+                .position(|v| self.severity(v) == Severity::Error)
+            {
+                violations.truncate(first + 1);
+                break;
+            }
+        }
+
+        violations
+    }
+
+    fn check_subject_length(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let len = if self.config.count_prefixes_in_length {
+            message.prefix_len + message.subject.chars().count()
+        } else {
+            message.subject.chars().count()
+        };
+        let max = self.config.subject_max_length;
+        if len > max {
+            vec![Violation::SubjectTooLong { len, max }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn check_subject_words(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let Some(max) = self.config.subject_max_words else {
+            return Vec::new();
+        };
+
+        let count = message.subject.split_whitespace().count();
+        if count > max {
+            vec![Violation::SubjectTooManyWords { count, max }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Subjects the config explicitly allows to stand alone, e.g. "Release",
+    // skip the rules that expect a verb followed by a description.
+    fn is_standalone(&self, subject: &str) -> bool {
+        self.config
+            .standalone_subjects
+            .iter()
+            .any(|allowed| allowed == subject.trim())
+    }
+
+    fn is_conventional(&self) -> bool {
+        self.config.convention == Convention::Conventional
+    }
+
+    fn check_standard_verb(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if self.is_conventional() || self.is_standalone(message.subject) {
+            return Vec::new();
+        }
+
+        let first_word = message.subject.split_whitespace().next().unwrap_or("");
+        if self.config.standard_verbs.contains_key(first_word) {
+            return Vec::new();
+        }
+
+        vec![Violation::UnknownVerb {
+            verb: first_word.to_string(),
+            allowed: self.allowed_verbs(),
+            suggestion: self.closest_verb(first_word),
+        }]
+    }
+
+    // The standard verb a few typed characters away from `word`, e.g. "Fix"
+    // for "Fxi" or "fix", ignoring case. Ties go to the first alphabetically.
+    fn closest_verb(&self, word: &str) -> Option<String> {
+        let word = word.to_lowercase();
+        self.config
+            .standard_verbs
+            .keys()
+            .map(|verb| (damerau_levenshtein(&word, &verb.to_lowercase()), verb))
+            .filter(|(distance, _)| *distance <= 2 && distance * 2 < word.chars().count().max(3))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, verb)| verb.clone())
+    }
+
+    fn check_subject_mood(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_subject_mood
+            || self.is_conventional()
+            || self.is_standalone(message.subject)
+        {
+            return Vec::new();
+        }
+
+        let first_word = message.subject.split_whitespace().next().unwrap_or("");
+        if self.config.standard_verbs.contains_key(first_word) {
+            return Vec::new();
+        }
+
+        imperative_form(first_word, &self.config.standard_verbs)
+            .map(|suggestion| Violation::NonImperativeSubject {
+                word: first_word.to_string(),
+                suggestion,
+            })
+            .into_iter()
+            .collect()
+    }
+
+    fn check_conventional_header(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.is_conventional() || self.is_standalone(message.subject) {
+            return Vec::new();
+        }
+
+        conventional_header_problem(message.subject, &self.config.conventional_types)
+            .map(|problem| Violation::MalformedHeader { problem })
+            .into_iter()
+            .collect()
+    }
+
+    fn check_description_present(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if self.is_conventional() || self.is_standalone(message.subject) {
+            return Vec::new();
+        }
+
+        let mut words = message.subject.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some(verb), None) => vec![Violation::MissingDescription {
+                verb: verb.to_string(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn check_full_stop(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if message.subject.ends_with('.') {
+            vec![Violation::SubjectFullStop]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Leading digits and symbols are skipped, so "3D support" passes, and a
+    // first word that is a number such as "3d" is taken as written. Letters
+    // from scripts without case can't be capitalised and always pass.
+    fn check_capitalised(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if self.is_conventional() {
+            return Vec::new();
+        }
+
+        let subject = message.subject;
+        let capitalised = if self.config.strict_capitalisation {
+            subject.chars().next().is_some_and(|c| c.is_uppercase())
+        } else {
+            subject.starts_with(|c: char| c.is_ascii_digit())
+                || !subject
+                    .chars()
+                    .find(|c| c.is_alphabetic())
+                    .is_some_and(|c| c.is_lowercase())
+        };
+
+        if capitalised {
+            Vec::new()
+        } else {
+            vec![Violation::SubjectNotCapitalised]
+        }
+    }
+
+    fn check_blank_line(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if message.missing_blank_line && self.config.require_blank_after_subject {
+            vec![Violation::MissingBlankLine]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // With `body_wrap = "soft"`, only list items and code, indented or
+    // fenced, are held to the limit; prose is left for the viewer to wrap.
+    fn check_body_line_length(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let max = self.config.body_max_line_length;
+        let mut in_fence = false;
+        let checked: Vec<bool> = message
+            .body
+            .lines()
+            .map(|line| {
+                let fence = line.trim_start().starts_with("```");
+                if fence {
+                    in_fence = !in_fence;
+                }
+                self.config.body_wrap == BodyWrap::Hard
+                    || fence
+                    || in_fence
+                    || is_list_item(line)
+                    || line.starts_with("    ")
+                    || line.starts_with('\t')
+            })
+            .collect();
+
+        message
+            .body
+            .lines()
+            .enumerate()
+            .filter(|(i, line)| checked[*i] && !line.is_empty() && line.len() > max)
+            .map(|(i, line)| Violation::BodyLineTooLong {
+                line: message.body_start + i,
+                len: line.len(),
+                max,
+            })
+            .collect()
+    }
+
+    // The word after the verb usually starts the description proper, as in
+    // "Add fixing the parser", and should be imperative too.
+    fn check_description_mood(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_description_mood {
+            return Vec::new();
+        }
+
+        let Some(word) = message.subject.split_whitespace().nth(1) else {
+            return Vec::new();
+        };
+        let lower = word.to_lowercase();
+
+        NON_IMPERATIVE_FORMS
+            .iter()
+            .find(|(form, _)| *form == lower)
+            .map(|(_, base)| Violation::NonImperativeDescription {
+                word: word.to_string(),
+                suggestion: base.to_string(),
+            })
+            .into_iter()
+            .collect()
+    }
+
+    // Looks for a conjunction followed by something that reads as a verb,
+    // so "Add login and fix logout" warns but "Add salt and pepper" doesn't.
+    fn check_single_change(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_single_change {
+            return Vec::new();
+        }
+
+        let is_verb = |word: &str| {
+            let lower = word.to_lowercase();
+            self.config
+                .standard_verbs
+                .keys()
+                .any(|verb| verb.to_lowercase() == lower)
+                || NON_IMPERATIVE_FORMS.iter().any(|(_, base)| *base == lower)
+        };
+
+        let words: Vec<&str> = message.subject.split_whitespace().collect();
+        words
+            .windows(2)
+            .skip(1)
+            .find(|pair| matches!(pair[0], "and" | "&" | "+") && is_verb(pair[1]))
+            .map(|pair| Violation::MultipleChanges {
+                conjunction: pair[0].to_string(),
+                second: pair[1].to_string(),
+            })
+            .into_iter()
+            .collect()
+    }
+
+    // A sentence break is a full stop, `!` or `?` followed by a space and a
+    // capital, so abbreviations such as "e.g. the parser" don't count.
+    fn check_single_sentence(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_single_sentence {
+            return Vec::new();
+        }
+
+        let subject = &message.subject;
+        subject
+            .match_indices(['.', '!', '?'])
+            .map(|(index, _)| subject[index + 1..].strip_prefix(' '))
+            .find_map(|rest| rest.filter(|rest| rest.starts_with(char::is_uppercase)))
+            .map(|rest| Violation::MultipleSentences {
+                rest: rest.to_string(),
+            })
+            .into_iter()
+            .collect()
+    }
+
+    fn check_body_lines(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let Some(max) = self.config.max_body_lines else {
+            return Vec::new();
+        };
+
+        let count = message.body.lines().filter(|line| !line.is_empty()).count();
+        if count > max {
+            vec![Violation::BodyTooLong { count, max }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn check_required_footers(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let verb = message.subject.split_whitespace().next().unwrap_or("");
+        let Some(required) = self.config.required_footers.get(verb) else {
+            return Vec::new();
+        };
+
+        let trailers = parse_trailers(message.body);
+        required
+            .iter()
+            .filter(|footer| {
+                !trailers
+                    .iter()
+                    .any(|(key, _)| key.eq_ignore_ascii_case(footer))
+            })
+            .map(|footer| Violation::MissingFooter {
+                verb: verb.to_string(),
+                footer: footer.clone(),
+            })
+            .collect()
+    }
+
+    // Sign-offs may be interleaved with other trailers, such as
+    // Co-authored-by, and repeated for each person who handled the change.
+    fn check_sign_off(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.require_sign_off {
+            return Vec::new();
+        }
+
+        let sign_offs: Vec<String> = parse_trailers(message.body)
+            .into_iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Signed-off-by"))
+            .map(|(_, value)| value)
+            .collect();
+        if sign_offs.is_empty() {
+            return vec![Violation::MissingSignOff];
+        }
+
+        sign_offs
+            .into_iter()
+            .filter(|value| !is_name_and_email(value))
+            .map(|value| Violation::MalformedSignOff { value })
+            .collect()
+    }
+
+    fn check_reference_keywords(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let allowed = &self.config.reference_keywords;
+        if allowed.is_empty() {
+            return Vec::new();
+        }
+
+        message
+            .body
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let keyword = reference_keyword(line)?;
+                if allowed.iter().any(|k| k.eq_ignore_ascii_case(keyword)) {
+                    return None;
+                }
+                Some(Violation::UnknownReferenceKeyword {
+                    line: message.body_start + i,
+                    keyword: keyword.to_string(),
+                    allowed: allowed.clone(),
+                })
+            })
+            .collect()
+    }
+
+    // Once the final paragraph holds any footer, every line in it must be a
+    // footer or an indented continuation of the one above.
+    fn check_footer_grammar(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_footer_grammar && !self.is_conventional() {
+            return Vec::new();
+        }
+
+        let lines: Vec<&str> = message.body.trim_end().lines().collect();
+        let start = lines
+            .iter()
+            .rposition(|line| line.trim().is_empty())
+            .map_or(0, |blank| blank + 1);
+        let paragraph = &lines[start..];
+        if !paragraph.iter().any(|line| parse_footer(line).is_some()) {
+            return Vec::new();
+        }
+
+        paragraph
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| parse_footer(line).is_none() && !line.starts_with([' ', '\t']))
+            .map(|(i, line)| Violation::MalformedFooter {
+                line: message.body_start + start + i,
+                text: line.to_string(),
+            })
+            .collect()
+    }
+
+    fn check_duplicate_subject(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let subject = message.subject.trim();
+        if self
+            .recent_subjects
+            .iter()
+            .any(|recent| recent.trim() == subject)
+        {
+            vec![Violation::DuplicateSubject {
+                subject: subject.to_string(),
+            }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Only warns when every staged file falls in one category with a
+    // configured verb, e.g. a docs-only change described as a "Fix".
+    fn check_category_verb(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.check_category_verb
+            || self.is_conventional()
+            || self.is_standalone(message.subject)
+        {
+            return Vec::new();
+        }
+
+        let Some((&category, rest)) = self.file_categories.split_first() else {
+            return Vec::new();
+        };
+        if rest.iter().any(|other| *other != category) {
+            return Vec::new();
+        }
+        let Some(expected) = self.config.category_verbs.get(category.key()) else {
+            return Vec::new();
+        };
+
+        let verb = message.subject.split_whitespace().next().unwrap_or("");
+        if verb == expected {
+            return Vec::new();
+        }
+
+        vec![Violation::CategoryVerbMismatch {
+            verb: verb.to_string(),
+            category: category.key().to_string(),
+            expected: expected.clone(),
+        }]
+    }
+
+    // Tabs are left alone; only invisible characters such as BEL or a stray
+    // carriage return are reported.
+    fn check_control_characters(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let subject = std::iter::once((1, message.subject));
+        let body = message
+            .body
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (message.body_start + i, line));
+
+        subject
+            .chain(body)
+            .flat_map(|(line_number, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|(_, c)| c.is_control() && *c != '\t')
+                    .map(move |(i, c)| Violation::ControlCharacter {
+                        line: line_number,
+                        column: i + 1,
+                        code: c as u32,
+                    })
+            })
+            .collect()
+    }
+
+    fn check_whitespace(&self, message: &ParsedMessage) -> Vec<Violation> {
+        let subject = std::iter::once((1, message.subject));
+        let body = message
+            .body
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (message.body_start + i, line));
+
+        let mut violations = Vec::new();
+        for (line_number, line) in subject.chain(body) {
+            if self.config.check_trailing_whitespace && line.ends_with([' ', '\t']) {
+                violations.push(Violation::TrailingWhitespace { line: line_number });
+            }
+            let tab = line.chars().position(|c| c == '\t');
+            if let Some(i) = tab.filter(|_| self.config.check_tabs) {
+                violations.push(Violation::TabCharacter {
+                    line: line_number,
+                    column: i + 1,
+                });
+            }
+        }
+        violations
+    }
+
+    fn check_final_newline(&self, message: &ParsedMessage) -> Vec<Violation> {
+        match message.trailing_newlines {
+            Some(count) if count != 1 && self.config.require_final_newline => {
+                vec![Violation::FinalNewline { count }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn check_edited_sections(&self, message: &ParsedMessage) -> Vec<Violation> {
+        if !self.config.require_edited_sections {
+            return Vec::new();
+        }
+
+        let templates = self.config.message_template.sections();
+        let headings: Vec<&str> = templates.iter().map(|t| section_heading(t)).collect();
+
+        split_sections(split_trailer_block(message.body).0, &headings)
+            .into_iter()
+            .filter_map(|(heading, body)| {
+                let template = templates.iter().find(|t| section_heading(t) == heading)?;
+                let default = template.split_once('\n').map_or("", |(_, rest)| rest);
+                (body.trim() == default.trim()).then(|| Violation::UneditedSection {
+                    section: section_name(template).to_string(),
+                })
+            })
+            .collect()
+    }
+
+    fn allowed_verbs(&self) -> Vec<(String, String)> {
+        let mut verbs: Vec<(String, String)> = self
+            .config
+            .standard_verbs
+            .iter()
+            .map(|(name, description)| (name.clone(), description.clone()))
+            .collect();
+        verbs.sort();
+        verbs
+    }
+
+    /// Like [`verify_message`](Self::verify_message), for a message file, or stdin
+    /// when the path is `-`.
+    pub fn verify_file(&self, file_path: &Path) -> Result<Vec<Finding>, String> {
+        Ok(self.findings(&self.file_violations(file_path)?))
+    }
+
+    /// Like [`message_violations`](Self::message_violations), for a message file.
+    pub fn file_violations(&self, file_path: &Path) -> Result<Vec<Violation>, String> {
+        let (message, _) = split_git_comments(&read_message(file_path)?, self.comment_char);
+        let trailing = message.len() - message.trim_end_matches('\n').len();
+        Ok(self.text_violations(&message, Some(trailing)))
+    }
+
+    // Corrects the problems that have a single obvious fix: trailing full
+    // stops, a lowercase verb, trailing whitespace and a missing blank line
+    // after the subject. Everything else is left for the author. Tags and a
+    // gitmoji before the verb are kept as written, and subjects git wrote
+    // itself, such as "fixup! ...", are never touched.
+    fn fix_message(&self, message: &str) -> String {
+        let mut lines: Vec<String> = message
+            .lines()
+            .map(|line| {
+                if self.config.check_trailing_whitespace {
+                    line.trim_end_matches([' ', '\t']).to_string()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        let Some(subject) = lines.first_mut() else {
+            return message.to_string();
+        };
+
+        let (rest, _) = self.strip_prefixes(subject);
+        if !is_git_generated(rest) {
+            let (prefix, rest) = subject.split_at(subject.len() - rest.len());
+            let rest = rest.trim_end_matches('.');
+            let mut chars = rest.chars();
+            // A Conventional Commits type is lowercase, so only its full
+            // stop is fixed
+            *subject = match chars.next() {
+                Some(first) if !self.is_conventional() => prefix
+                    .chars()
+                    .chain(first.to_uppercase())
+                    .chain(chars)
+                    .collect(),
+                _ => format!("{}{}", prefix, rest),
+            };
+        }
+
+        if self.config.require_blank_after_subject
+            && lines.get(1).is_some_and(|line| !line.is_empty())
+        {
+            lines.insert(1, String::new());
+        }
+
+        let mut fixed = lines.join("\n");
+        if self.config.require_final_newline {
+            fixed.truncate(fixed.trim_end_matches('\n').len());
+            fixed.push('\n');
+        } else if message.ends_with('\n') {
+            fixed.push('\n');
+        }
+        fixed
+    }
+
+    /// Applies `fix_message` to a file in place, returning whether it changed.
+    /// Git's comment lines are moved below the fixed message, unchanged.
+    pub fn fix_file(&self, file_path: &Path) -> Result<bool, String> {
+        if file_path == Path::new("-") {
+            return Err("Cannot fix a message read from stdin; pass a file instead".to_string());
+        }
+        let (message, comments) = split_git_comments(&read_message(file_path)?, self.comment_char);
+        let mut fixed = self.fix_message(&message);
+        if fixed == message {
+            return Ok(false);
+        }
+
+        if !comments.is_empty() {
+            fixed.push('\n');
+            fixed.push_str(&comments);
+        }
+        fs::write(file_path, fixed).map_err(|e| format!("Failed to write file: {}", e))?;
+        Ok(true)
+    }
+
+    /// Verifies a JSON file holding `subject` and `body` fields.
+    pub fn verify_json_file(&self, file_path: &Path) -> Result<Vec<Finding>, String> {
+        Ok(self.findings(&self.json_file_violations(file_path)?))
+    }
+
+    /// Every violation in a JSON message file.
+    pub fn json_file_violations(&self, file_path: &Path) -> Result<Vec<Violation>, String> {
+        let contents = read_message(file_path)?;
+        let message: StructuredMessage =
+            serde_json::from_str(&contents).map_err(|e| format!("Invalid JSON message: {}", e))?;
+        Ok(self.structured_violations(&message))
+    }
+
+    /// Renders results as a JSON object: `valid`, then the `errors` and
+    /// `warnings` as display text.
+    pub fn json_report(&self, violations: &[Violation]) -> serde_json::Value {
+        let findings = self.findings(violations);
+        let rendered = |severity: Severity| -> Vec<&str> {
+            findings
+                .iter()
+                .filter(|finding| finding.severity == severity)
+                .map(|finding| finding.message.as_str())
+                .collect()
+        };
+        let errors = rendered(Severity::Error);
+
+        serde_json::json!({
+            "valid": errors.is_empty(),
+            "errors": errors,
+            "warnings": rendered(Severity::Warning),
+        })
+    }
+
+    /// Renders results as a JUnit report with a test suite per named message,
+    /// in which every rule the config enables is a test case and every
+    /// error-level violation of it a failure.
+    pub fn junit_report(&self, suites: &[(String, Vec<Violation>)]) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        for (name, violations) in suites {
+            self.junit_suite(&mut xml, name, violations);
+        }
+        xml.push_str("</testsuites>");
+        xml
+    }
+
+    // Whether the config leaves rule `id` switched on. Rules that never run
+    // aren't reported as passing.
+    fn rule_runs(&self, id: &str) -> bool {
+        let config = &self.config;
+        let conventional = self.is_conventional();
+        match id {
+            "subject-max-words" => config.subject_max_words.is_some(),
+            "standard-verb" | "subject-description" | "subject-capitalised" => !conventional,
+            "subject-mood" => config.check_subject_mood && !conventional,
+            "conventional-header" => conventional,
+            "blank-line-after-subject" => config.require_blank_after_subject,
+            "body-max-lines" => config.max_body_lines.is_some(),
+            "final-newline" => config.require_final_newline,
+            "unedited-section" => config.require_edited_sections,
+            "description-mood" => config.check_description_mood,
+            "single-change" => config.check_single_change,
+            "single-sentence" => config.check_single_sentence,
+            "required-footer" => !config.required_footers.is_empty(),
+            "sign-off" => config.require_sign_off,
+            "reference-keyword" => !config.reference_keywords.is_empty(),
+            "footer-grammar" => config.check_footer_grammar || conventional,
+            "duplicate-subject" => !self.recent_subjects.is_empty(),
+            "category-verb" => config.check_category_verb && !conventional,
+            _ => true,
+        }
+    }
+
+    fn junit_suite(&self, xml: &mut String, name: &str, violations: &[Violation]) {
+        let failures = violations
+            .iter()
+            .filter(|v| self.severity(v) == Severity::Error)
+            .count();
+        // An empty message stops the check before any other rule
+        let empty = violations
+            .iter()
+            .any(|v| matches!(v, Violation::EmptyMessage));
+        let ran: Vec<&RuleInfo> = RULES
+            .iter()
+            .filter(|rule| {
+                if empty {
+                    rule.id == "empty-message"
+                } else {
+                    self.rule_runs(rule.id)
+                }
+            })
+            .collect();
+
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n",
+            xml_escape(name),
+            ran.len(),
+            failures
+        ));
+
+        for rule in ran {
+            let found: Vec<&Violation> = violations
+                .iter()
+                .filter(|v| v.rule_id() == rule.id)
+                .collect();
+            let open = format!(
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                xml_escape(name),
+                rule.id
+            );
+
+            if found.is_empty() {
+                xml.push_str(&format!("{}/>\n", open));
+                continue;
+            }
+
+            xml.push_str(&format!("{}>\n", open));
+            for violation in found {
+                let message = xml_escape(&violation.render(&self.config.custom_messages));
+                match self.severity(violation) {
+                    Severity::Error => xml.push_str(&format!(
+                        "      <failure type=\"{}\" message=\"{}\"/>\n",
+                        rule.id, message
+                    )),
+                    Severity::Warning => xml.push_str(&format!(
+                        "      <system-out>warning: {}</system-out>\n",
+                        message
+                    )),
+                }
+            }
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+}
+
+/// Where the diff being described comes from.
+pub enum DiffSource {
+    /// The index, as `git commit` would record it
+    Staged,
+    /// Unstaged edits in the working tree
+    Worktree,
+    /// An existing commit, as shown by `git show`
+    Commit(String),
+    /// Any revspec `git diff` accepts, e.g. `HEAD~3..HEAD` or a single commit
+    Range(String),
+    /// The last commit with the staged changes on top, as
+    /// `git commit --amend` would record it
+    Amend,
+}
+
+impl DiffSource {
+    fn git_args(&self) -> Vec<&str> {
+        match self {
+            DiffSource::Staged => vec!["diff", "--cached"],
+            DiffSource::Worktree => vec!["diff"],
+            DiffSource::Commit(rev) => vec!["show", "--format=", rev],
+            DiffSource::Range(spec) => vec!["diff", spec],
+            DiffSource::Amend => vec!["diff", "--cached"],
+        }
+    }
+
+    // The two sides of a range such as "A..B"; a single revision is
+    // compared against the working tree, which has no revision of its own.
+    fn range_sides(spec: &str) -> (&str, Option<&str>) {
+        match spec.split_once("...").or_else(|| spec.split_once("..")) {
+            Some((from, to)) => (
+                if from.is_empty() { "HEAD" } else { from },
+                Some(if to.is_empty() { "HEAD" } else { to }),
+            ),
+            None => (spec, None),
+        }
+    }
+}
+
+/// Reads changes from git and works out what they mean for a message.
+pub struct GitDiffAnalyzer {
+    config: Config,
+    path_filter: Option<GlobSet>,
+    ignore: IgnoreRules,
+    source: DiffSource,
+    repo: Option<PathBuf>,
+}
+
+impl GitDiffAnalyzer {
+    /// The config the analyzer was built with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// An analyzer of the staged changes.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            path_filter: None,
+            ignore: IgnoreRules::default(),
+            source: DiffSource::Staged,
+            repo: None,
+        }
+    }
+
+    /// Run every git command in the repository at `path`, as `git -C` does,
+    /// instead of the current directory.
+    pub fn with_repo(mut self, path: &Path) -> Self {
+        self.repo = Some(path.to_path_buf());
+        self
+    }
+
+    /// Read the diff from `source` instead of the staged changes.
+    pub fn with_source(mut self, source: DiffSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Restricts analysis to files matching any of `patterns`.
+    pub fn with_path_filter(mut self, patterns: &[String]) -> Self {
+        self.path_filter = Some(build_globset(patterns));
+        self
+    }
+
+    /// Leaves out the files matched by the gitignore-style patterns in `path`,
+    /// e.g. lockfiles and snapshots. A missing file ignores nothing.
+    pub fn with_ignore_file(mut self, path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => self.ignore = IgnoreRules::parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+        Ok(self)
+    }
+
+    // Whether `path` passes the path filter and isn't ignored.
+    fn wanted(&self, path: &str) -> bool {
+        self.path_filter
+            .as_ref()
+            .is_none_or(|filter| filter.is_match(path))
+            && !self.ignore.is_ignored(path)
+    }
+
+    fn git(&self) -> Command {
+        let mut command = Command::new("git");
+        if let Some(repo) = &self.repo {
+            command.arg("-C").arg(repo);
+        }
+        command
+    }
+
+    // The git command that prints the diff being described.
+    fn diff_command(&self) -> Command {
+        let mut command = self.git();
+        command.args(self.source.git_args());
+        if matches!(self.source, DiffSource::Amend) {
+            command.arg(self.amend_base());
+        }
+        command
+    }
+
+    // What an amended commit is compared against: the last commit's parent,
+    // or the empty tree when the last commit is the first one.
+    fn amend_base(&self) -> String {
+        let resolve = |args: &[&str]| {
+            self.run(self.git().args(args))
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        resolve(&["rev-parse", "--verify", "--quiet", "HEAD^"])
+            .or_else(|| resolve(&["hash-object", "-t", "tree", "/dev/null"]))
+            .unwrap_or_else(|| "HEAD^".to_string())
+    }
+
+    // Where a path git reported, relative to the repository root, is on disk.
+    fn in_repo(&self, path: impl AsRef<Path>) -> PathBuf {
+        match &self.repo {
+            Some(repo) => repo.join(path),
+            None => path.as_ref().to_path_buf(),
+        }
+    }
+
+    // Runs a git command, giving up after `git_timeout_secs` so a git
+    // waiting on a lock or a prompt can't hang the tool. 0 waits forever.
+    fn run(&self, command: &mut Command) -> io::Result<Output> {
+        match self.config.git_timeout_secs {
+            0 => command.output(),
+            secs => output_with_timeout(command, Duration::from_secs(secs)),
+        }
+    }
+
+    /// The diff to describe, from the index or the chosen commit. A missing
+    /// git, a directory outside any repository and a diff that isn't UTF-8
+    /// each have their own error; an empty diff is not an error.
+    pub fn get_git_diff(&self) -> Result<String, String> {
+        let output = self
+            .run(&mut self.diff_command())
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    "git not found; install it or add it to PATH".to_string()
+                }
+                io::ErrorKind::TimedOut => e.to_string(),
+                _ => format!("Failed to run git: {}", e),
+            })?;
+        if !output.status.success() {
+            // Outside a repository git diff falls back to comparing two
+            // paths, so its complaint is about usage rather than the repo
+            let inside = self
+                .run(self.git().args(["rev-parse", "--git-dir"]))
+                .is_ok_and(|output| output.status.success());
+            if !inside {
+                let dir = self
+                    .repo
+                    .as_ref()
+                    .map_or("The current directory".to_string(), |repo| {
+                        repo.display().to_string()
+                    });
+                return Err(format!("{} is not a git repository", dir));
+            }
+            return Err(format!(
+                "git failed to produce the diff: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        String::from_utf8(output.stdout).map_err(|e| {
+            format!(
+                "The diff is not valid UTF-8 (at byte {}); mark files that aren't text as binary in .gitattributes",
+                e.utf8_error().valid_up_to()
+            )
+        })
+    }
+
+    // The checked-out branch, or None on a detached HEAD.
+    fn current_branch(&self) -> Option<String> {
+        let output = self
+            .run(self.git().args(["rev-parse", "--abbrev-ref", "HEAD"]))
+            .ok()?;
+        let branch = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        (!branch.is_empty() && branch != "HEAD").then_some(branch)
+    }
+
+    /// Extracts a ticket from the current branch name with
+    /// `branch_ticket_pattern`, e.g. `PROJ-123` from `feature/PROJ-123-login`
+    /// or `#123` from `123-fix-login`. The first capture group is used when the
+    /// pattern has one, otherwise the whole match.
+    pub fn get_branch_ticket(&self) -> Option<String> {
+        let pattern = &self.config.branch_ticket_pattern;
+        if pattern.is_empty() {
+            return None;
+        }
+        let regex = match Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(e) => {
+                warn!(
+                    "Ignoring invalid branch_ticket_pattern {:?}: {}",
+                    pattern, e
+                );
+                return None;
+            }
+        };
+
+        let branch = self.current_branch()?;
+        let captures = regex.captures(&branch)?;
+        let ticket = captures.get(1).or_else(|| captures.get(0))?.as_str();
+        if ticket.chars().all(|c| c.is_ascii_digit()) {
+            Some(format!("#{}", ticket))
+        } else {
+            Some(ticket.to_string())
+        }
+    }
+
+    /// Finds the most recent issue reference in commits that exist only on the
+    /// current branch, so references never leak in from unrelated work.
+    pub fn get_branch_reference(&self) -> Option<String> {
+        let branch = self.current_branch()?;
+
+        let output = self
+            .run(
+                self.git()
+                    .args(["log", "--format=%B%x00", "HEAD", "--not"])
+                    .arg(format!("--exclude={}", branch))
+                    .arg("--branches"),
+            )
+            .ok()?;
+        let log = String::from_utf8(output.stdout).ok()?;
+
+        log.split('\0').find_map(find_issue_reference)
+    }
+
+    /// The latest `history_count` commits before this change that touched any
+    /// of `files`, as `<short hash> <subject>` lines.
+    pub fn get_related_commits(&self, files: &[&String]) -> Vec<String> {
+        let start = match &self.source {
+            DiffSource::Staged | DiffSource::Worktree => "HEAD".to_string(),
+            DiffSource::Commit(rev) => format!("{}^", rev),
+            DiffSource::Range(spec) => DiffSource::range_sides(spec).0.to_string(),
+            DiffSource::Amend => self.amend_base(),
+        };
+
+        let output = self.run(
+            self.git()
+                .arg("log")
+                .arg(format!("-n{}", self.config.history_count))
+                .args(["--format=%h %s", &start, "--"])
+                .args(files),
+        );
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| line.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The full message of the last commit, or `None` when there is no
+    /// commit yet or git can't be run.
+    pub fn get_last_message(&self) -> Option<String> {
+        let output = self
+            .run(self.git().args(["log", "-1", "--format=%B"]))
+            .ok()
+            .filter(|output| output.status.success())?;
+
+        String::from_utf8(output.stdout).ok()
+    }
+
+    /// The paths the last commit touched, by destination for renames.
+    pub fn last_commit_files(&self) -> BTreeSet<String> {
+        let output = self.run(
+            self.git()
+                .args(["show", "--format=", "--name-status", "HEAD"]),
+        );
+        match output {
+            Ok(output) if output.status.success() => self
+                .analyse_name_status(&String::from_utf8_lossy(&output.stdout))
+                .into_keys()
+                .collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Warns about binary files whose new content exceeds
+    /// `binary_size_limit` bytes, which are usually staged by accident. A
+    /// limit of 0 turns the check off.
+    pub fn binary_advisories(&self) -> Vec<String> {
+        let limit = self.config.binary_size_limit;
+        if limit == 0 {
+            return Vec::new();
+        }
+
+        let Ok(output) = self.run(self.diff_command().args(["--numstat", "--diff-filter=d"]))
+        else {
+            return Vec::new();
+        };
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            // Binary files are listed with "-" in place of line counts
+            .filter_map(|line| line.strip_prefix("-\t-\t"))
+            .filter(|path| self.wanted(path))
+            .filter_map(|path| large_binary_advisory(path, self.blob_size(path)?, limit))
+            .collect()
+    }
+
+    fn blob_size(&self, path: &str) -> Option<u64> {
+        let object = match &self.source {
+            DiffSource::Staged | DiffSource::Amend => format!(":{}", path),
+            DiffSource::Commit(rev) => format!("{}:{}", rev, path),
+            DiffSource::Range(spec) => match DiffSource::range_sides(spec) {
+                (_, Some(to)) => format!("{}:{}", to, path),
+                (_, None) => return fs::metadata(self.in_repo(path)).ok().map(|m| m.len()),
+            },
+            DiffSource::Worktree => return fs::metadata(self.in_repo(path)).ok().map(|m| m.len()),
+        };
+        let output = self
+            .run(self.git().args(["cat-file", "-s", &object]))
+            .ok()?;
+
+        String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+    }
+
+    /// Where git looks for hooks, honouring `core.hooksPath` and worktrees.
+    pub fn hooks_dir(&self) -> Option<PathBuf> {
+        let output = self
+            .run(self.git().args(["rev-parse", "--git-path", "hooks"]))
+            .ok()?;
+        let dir = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        (output.status.success() && !dir.is_empty()).then(|| self.in_repo(&dir))
+    }
+
+    /// Messages of the commits reachable from HEAD but not from `base`,
+    /// oldest first.
+    pub fn get_messages_since(&self, base: &str) -> Option<Vec<String>> {
+        let output = self
+            .run(
+                self.git()
+                    .args(["log", "--reverse", "--format=%B%x00"])
+                    .arg(format!("{}..HEAD", base)),
+            )
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let log = String::from_utf8(output.stdout).ok()?;
+        Some(
+            log.split('\0')
+                .map(|message| message.trim().to_string())
+                .filter(|message| !message.is_empty())
+                .collect(),
+        )
+    }
+
+    /// The last `count` commits as `(short hash, message)` pairs, newest first.
+    pub fn get_recent_messages(&self, count: usize) -> Option<Vec<(String, String)>> {
+        // A record separator ends each commit, and a NUL ends its hash
+        let output = self
+            .run(
+                self.git()
+                    .arg("log")
+                    .arg(format!("-n{}", count))
+                    .arg("--format=%h%x00%B%x1e"),
+            )
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let log = String::from_utf8(output.stdout).ok()?;
+        Some(
+            log.split('\x1e')
+                .filter_map(|record| record.trim_start().split_once('\0'))
+                .map(|(hash, message)| (hash.to_string(), message.trim().to_string()))
+                .collect(),
+        )
+    }
+
+    /// The character `core.commentChar` sets for comment lines in a message
+    /// file, or `#` when it is unset or `auto`.
+    pub fn comment_char(&self) -> char {
+        match self.git_config("core.commentChar").as_deref() {
+            None | Some("auto") => '#',
+            Some(value) => value.chars().next().unwrap_or('#'),
+        }
+    }
+
+    /// The pager `core.pager` names, if it is set.
+    pub fn core_pager(&self) -> Option<String> {
+        self.git_config("core.pager")
+    }
+
+    // A git config value, or `None` when it is unset or empty.
+    fn git_config(&self, key: &str) -> Option<String> {
+        let output = self
+            .run(self.git().args(["config", key]))
+            .ok()
+            .filter(|output| output.status.success())?;
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// The subjects of the last `count` commits, newest first.
+    pub fn get_recent_subjects(&self, count: usize) -> Vec<String> {
+        match self.run(
+            self.git()
+                .arg("log")
+                .arg(format!("-n{}", count))
+                .arg("--format=%s"),
+        ) {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|line| line.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Tries, in order: the `default_branch` setting, the branch origin's HEAD
+    /// points at, git's init.defaultBranch, then the first of main, master
+    /// and trunk that exists locally.
+    pub fn detect_default_branch(&self) -> Option<String> {
+        if let Some(branch) = &self.config.default_branch {
+            return Some(branch.clone());
+        }
+
+        let stdout = |args: &[&str]| {
+            let output = self.run(self.git().args(args)).ok()?;
+            let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+            (output.status.success() && !value.is_empty()).then_some(value)
+        };
+        let exists = |branch: &str| {
+            stdout(&[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/heads/{}", branch),
+            ])
+            .is_some()
+        };
+
+        if let Some(remote_head) = stdout(&["symbolic-ref", "--short", "refs/remotes/origin/HEAD"])
+        {
+            return Some(remote_head);
+        }
+        if let Some(branch) = stdout(&["config", "init.defaultBranch"]).filter(|b| exists(b)) {
+            return Some(branch);
+        }
+        ["main", "master", "trunk"]
+            .iter()
+            .find(|branch| exists(branch))
+            .map(|branch| branch.to_string())
+    }
+
+    /// `Name <email>` from git's user.name and user.email.
+    pub fn get_identity(&self) -> Option<String> {
+        let value = |key: &str| {
+            let output = self.run(self.git().args(["config", key])).ok()?;
+            let value = String::from_utf8(output.stdout).ok()?.trim().to_string();
+            (!value.is_empty()).then_some(value)
+        };
+
+        Some(format!(
+            "{} <{}>",
+            value("user.name")?,
+            value("user.email")?
+        ))
+    }
+
+    /// `git diff --name-status` output for the same changes as the diff.
+    pub fn get_name_status(&self) -> Option<String> {
+        let output = self.run(self.diff_command().arg("--name-status")).ok()?;
+
+        String::from_utf8(output.stdout).ok()
+    }
+
+    /// Parses `--name-status` lines such as "M\tsrc/main.rs" or
+    /// "R096\told.rs\tnew.rs", keyed by the destination path.
+    pub fn analyse_name_status(&self, output: &str) -> BTreeMap<String, FileStatus> {
+        let statuses: BTreeMap<String, FileStatus> = output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                let letter = fields.next()?.chars().next()?;
+                let path = unquote_path(fields.next_back()?);
+                Some((path, FileStatus::from_letter(letter)))
+            })
+            .filter(|(path, _)| self.wanted(path))
+            .collect();
+
+        let files: Vec<String> = statuses.keys().cloned().collect();
+        let attributes = self.linguist_attributes(&files);
+        statuses
+            .into_iter()
+            .filter(|(path, _)| !attributes.get(path).is_some_and(|a| a.is_ignored()))
+            .collect()
+    }
+
+    // Asks git for the linguist attributes of `files`, so nested and
+    // global attribute files are honoured as well as the top-level one.
+    fn linguist_attributes(&self, files: &[String]) -> BTreeMap<String, LinguistAttributes> {
+        if files.is_empty() {
+            return BTreeMap::new();
+        }
+
+        let Ok(output) = self.run(
+            self.git()
+                .args([
+                    "check-attr",
+                    "linguist-generated",
+                    "linguist-vendored",
+                    "linguist-language",
+                    "--",
+                ])
+                .args(files),
+        ) else {
+            return BTreeMap::new();
+        };
+
+        parse_linguist_attributes(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    // Tests are checked first so that e.g. `tests/README.md` counts as a
+    // test. A `linguist-language` override decides between docs and source
+    // ahead of the file patterns.
+    fn categorise(&self, path: &str, language: Option<&str>) -> FileCategory {
+        const DOC_LANGUAGES: &[&str] = &["markdown", "restructuredtext", "asciidoc", "text"];
+
+        if build_globset(&self.config.test_file_patterns).is_match(path) {
+            FileCategory::Tests
+        } else if let Some(language) = language {
+            if DOC_LANGUAGES.contains(&language.to_lowercase().as_str()) {
+                FileCategory::Docs
+            } else {
+                FileCategory::Source
+            }
+        } else if build_globset(&self.config.doc_file_patterns).is_match(path) {
+            FileCategory::Docs
+        } else if build_globset(&self.config.source_file_patterns).is_match(path) {
+            FileCategory::Source
+        } else {
+            FileCategory::Other
+        }
+    }
+
+    /// Dependencies added, removed or bumped in known manifests, one line
+    /// each, e.g. "Bumped serde 1.0.215 -> 1.0.216".
+    pub fn dependency_changes(&self, changes: &GitChanges) -> Vec<String> {
+        let manifests: BTreeSet<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_removals.keys())
+            .collect();
+
+        let mut lines = Vec::new();
+        for manifest in manifests {
+            let name = Path::new(manifest)
+                .file_name()
+                .map_or("", |name| name.to_str().unwrap_or(""));
+            let versions = |map: &BTreeMap<String, Vec<String>>| -> BTreeMap<String, String> {
+                map.get(manifest)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|line| parse_dependency(name, line))
+                    .collect()
+            };
+            let added = versions(&changes.file_changes);
+            let removed = versions(&changes.file_removals);
+
+            for (dependency, new) in &added {
+                match removed.get(dependency) {
+                    Some(old) if old != new => {
+                        lines.push(format!("Bumped {} {} -> {}", dependency, old, new))
+                    }
+                    Some(_) => {}
+                    None => lines.push(format!("Added {} {}", dependency, new)),
+                }
+            }
+            for (dependency, old) in &removed {
+                if !added.contains_key(dependency) {
+                    lines.push(format!("Removed {} {}", dependency, old));
+                }
+            }
+        }
+        lines
+    }
+
+    /// The category of each staged file.
+    pub fn file_categories(&self) -> Vec<FileCategory> {
+        let Some(output) = self.get_name_status() else {
+            return Vec::new();
+        };
+
+        let files: Vec<String> = self.analyse_name_status(&output).into_keys().collect();
+        let attributes = self.linguist_attributes(&files);
+        files
+            .iter()
+            .map(|path| {
+                let language = attributes.get(path).and_then(|a| a.language.as_deref());
+                self.categorise(path, language)
+            })
+            .collect()
+    }
+
+    /// Files with edits that aren't staged, or None if git failed.
+    pub fn get_unstaged_files(&self) -> Option<Vec<String>> {
+        let output = self.run(self.git().args(["diff", "--name-only"])).ok()?;
+
+        let files = String::from_utf8(output.stdout).ok()?;
+        Some(files.lines().map(|s| s.to_string()).collect())
+    }
+
+    /// Collects the added and removed lines of each file in `diff_output`,
+    /// and any that look like breaking changes.
+    pub fn analyse_diff(&self, diff_output: &str) -> GitChanges {
+        let mut file_changes = BTreeMap::new();
+        let mut file_removals = BTreeMap::new();
+        let mut renames = Vec::new();
+        let mut binary_files = BTreeMap::new();
+        let mut line_counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+        let mut breaking_changes = Vec::new();
+        let mut current_file = None;
+        let mut similarity = None;
+        let mut renamed_from = None;
+        // The `---`/`+++` header lines come before a file's first hunk, so
+        // inside a hunk a line such as "--- a comment" is a removed line.
+        let mut in_hunk = false;
+
+        let files: Vec<String> = diff_output
+            .lines()
+            .filter_map(|line| line.strip_prefix("diff --git "))
+            .filter_map(parse_diff_header)
+            .collect();
+        let attributes = self.linguist_attributes(&files);
+
+        let included = |file: &String| {
+            self.wanted(file) && !attributes.get(file).is_some_and(|a| a.is_ignored())
+        };
+
+        // A rename's extended header names both paths exactly, spaces and
+        // all, so it overrides whatever the `diff --git` line parsed to.
+        for line in diff_output.lines() {
+            if let Some(header) = line.strip_prefix("diff --git ") {
+                current_file = parse_diff_header(header).filter(included);
+                similarity = None;
+                renamed_from = None;
+                in_hunk = false;
+            } else if line.starts_with("@@") {
+                in_hunk = true;
+            } else if let Some(index) = line.strip_prefix("similarity index ") {
+                similarity = Some(index.to_string());
+            } else if let Some(from) = line.strip_prefix("rename from ") {
+                renamed_from = Some(unquote_path(from));
+            } else if let Some(to) = line.strip_prefix("rename to ") {
+                current_file = Some(unquote_path(to)).filter(included);
+                if let (Some(to), Some(from)) = (&current_file, renamed_from.take()) {
+                    breaking_changes.push(match &similarity {
+                        Some(index) => format!("* Renamed {} -> {} ({} similar)", from, to, index),
+                        None => format!("* Renamed {} -> {}", from, to),
+                    });
+                    renames.push((from, to.clone()));
+                }
+            } else if line.starts_with("Binary files ") && line.ends_with(" differ") {
+                // Nothing else in this file's section belongs to a text file
+                if let Some(file) = current_file.take() {
+                    let status = if line.starts_with("Binary files /dev/null and ") {
+                        FileStatus::Added
+                    } else if line.ends_with(" and /dev/null differ") {
+                        FileStatus::Deleted
+                    } else {
+                        FileStatus::Modified
+                    };
+                    binary_files.insert(file, status);
+                }
+            } else if in_hunk && line.starts_with('+') {
+                if let Some(file) = &current_file {
+                    line_counts.entry(file.clone()).or_default().0 += 1;
+                    let change = line[1..].trim().to_string();
+                    if !change.is_empty() {
+                        file_changes
+                            .entry(file.clone())
+                            .or_insert_with(Vec::new)
+                            .push(change.clone());
+
+                        if self.is_breaking_change(&change) {
+                            breaking_changes
+                                .push(format!("* Breaking change in {}:\n  {}", file, change));
+                        }
+                    }
+                }
+            } else if in_hunk && line.starts_with('-') {
+                if let Some(file) = &current_file {
+                    line_counts.entry(file.clone()).or_default().1 += 1;
+                    let change = line[1..].trim().to_string();
+                    if !change.is_empty() {
+                        file_removals
+                            .entry(file.clone())
+                            .or_insert_with(Vec::new)
+                            .push(change.clone());
+
+                        if self.is_breaking_change(&change) {
+                            breaking_changes.push(format!(
+                                "* Breaking change in {} (removed):\n  {}",
+                                file, change
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let advisories = if self.config.test_advisory {
+            self.test_advisories(&file_changes)
+        } else {
+            Vec::new()
+        };
+
+        GitChanges {
+            file_changes,
+            file_removals,
+            renames,
+            binary_files,
+            line_counts,
+            breaking_changes,
+            advisories,
+            reference: None,
+            file_status: BTreeMap::new(),
+            related_commits: Vec::new(),
+            co_authors: Vec::new(),
+            verb: None,
+        }
+    }
+
+    // Warns when source files changed without any test file alongside them.
+    fn test_advisories(&self, file_changes: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+        let sources = build_globset(&self.config.source_file_patterns);
+        let tests = build_globset(&self.config.test_file_patterns);
+
+        if file_changes.keys().any(|file| tests.is_match(file)) {
+            return Vec::new();
+        }
+
+        let mut untested: Vec<&str> = file_changes
+            .keys()
+            .filter(|file| sources.is_match(file))
+            .map(|file| file.as_str())
+            .collect();
+        if untested.is_empty() {
+            return Vec::new();
+        }
+        untested.sort();
+
+        vec![format!(
+            "Source changed without tests: {}",
+            untested.join(", ")
+        )]
+    }
+
+    // Indicators must be whole words, so "changelog" and "unbreakable" don't
+    // count. Underscores separate words too, as in `remove_user`.
+    fn is_breaking_change(&self, change: &str) -> bool {
+        let breaking_indicators = [
+            "remove",
+            "delete",
+            "deprecate",
+            "break",
+            "change",
+            "rename",
+            "refactor",
+            "drop",
+            "migrate",
+        ];
+
+        change
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|token| breaking_indicators.contains(&token))
+    }
+
+    /// Major when the diff has breaking changes, minor when its verb is Add,
+    /// patch otherwise.
+    pub fn suggest_bump(&self, changes: &GitChanges) -> VersionBump {
+        let verb = self.determine_commit_verb(changes).verb;
+        if !changes.breaking_changes.is_empty() {
+            VersionBump::Major
+        } else if verb == "Add" {
+            VersionBump::Minor
+        } else {
+            VersionBump::Patch
+        }
+    }
+
+    /// The verb for `changes`, with what decided it: the author's pick, git's
+    /// status letters, or the indicator words found in the added lines.
+    pub fn determine_commit_verb(&self, changes: &GitChanges) -> VerbChoice {
+        let candidates = self.verb_candidates(changes);
+        let (verb, reason) = match (&changes.verb, self.status_verb(changes)) {
+            (Some(verb), _) => (verb.clone(), VerbReason::Given),
+            (None, Some("Add")) => ("Add".to_string(), VerbReason::AllAdded),
+            (None, Some(verb)) => (verb.to_string(), VerbReason::AllDeleted),
+            (None, None) => match candidates.first() {
+                Some(candidate) => (candidate.verb.clone(), VerbReason::Indicators),
+                None => ("Add".to_string(), VerbReason::Default),
+            },
+        };
+
+        let matches = self
+            .indicator_matches(changes)
+            .into_iter()
+            .filter(|m| {
+                reason == VerbReason::Indicators && self.indicator_verb(&m.indicator) == verb
+            })
+            .collect();
+        VerbChoice {
+            verb,
+            reason,
+            matches,
+            candidates,
+        }
+    }
+
+    fn indicator_verb(&self, indicator: &str) -> &str {
+        self.config
+            .verb_mapping
+            .get(indicator)
+            .map_or("Add", |verb| verb.as_str())
+    }
+
+    // Every indicator word in every added line, file by file.
+    fn indicator_matches(&self, changes: &GitChanges) -> Vec<IndicatorMatch> {
+        let mut matches = Vec::new();
+        for (file, lines) in &changes.file_changes {
+            for line in lines {
+                let lower = line.to_lowercase();
+                for (indicator, words) in &self.config.indicators {
+                    for word in words {
+                        let count = lower.matches(word.as_str()).count();
+                        if count > 0 {
+                            matches.push(IndicatorMatch {
+                                indicator: indicator.clone(),
+                                word: word.clone(),
+                                file: file.clone(),
+                                line: line.clone(),
+                                count,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    // Git's own status letters are authoritative when every file agrees
+    fn status_verb(&self, changes: &GitChanges) -> Option<&'static str> {
+        let statuses: Vec<FileStatus> = changes.file_status.values().copied().collect();
+        if statuses.is_empty() {
+            None
+        } else if statuses.iter().all(|s| *s == FileStatus::Added) {
+            Some("Add")
+        } else if statuses.iter().all(|s| *s == FileStatus::Deleted) {
+            Some("Cut")
+        } else {
+            None
+        }
+    }
+
+    // Scores each mapped verb by how often its indicator words appear in the
+    // added lines, best first, with ties broken alphabetically.
+    fn verb_candidates(&self, changes: &GitChanges) -> Vec<VerbCandidate> {
+        let matches = self.indicator_matches(changes);
+
+        let mut candidates: Vec<VerbCandidate> = Vec::new();
+        for (indicator, words) in &self.config.indicators {
+            let verb = self.indicator_verb(indicator);
+            for word in words {
+                let count: usize = matches
+                    .iter()
+                    .filter(|m| m.indicator == *indicator && m.word == *word)
+                    .map(|m| m.count)
+                    .sum();
+                if count == 0 {
+                    continue;
+                }
+                match candidates.iter_mut().find(|c| c.verb == verb) {
+                    Some(candidate) => {
+                        candidate.matches += count;
+                        candidate.words.push(word.clone());
+                    }
+                    None => candidates.push(VerbCandidate {
+                        verb: verb.to_string(),
+                        matches: count,
+                        words: vec![word.clone()],
+                    }),
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| b.matches.cmp(&a.matches).then(a.verb.cmp(&b.verb)));
+        candidates
+    }
+
+    /// The candidates worth asking about: only when status letters don't
+    /// settle it and the runner-up has at least half the leader's matches.
+    pub fn ambiguous_verbs(&self, changes: &GitChanges) -> Option<Vec<VerbCandidate>> {
+        if self.status_verb(changes).is_some() {
+            return None;
+        }
+
+        let candidates = self.verb_candidates(changes);
+        match candidates.as_slice() {
+            [first, second, ..] if second.matches * 2 >= first.matches => Some(candidates),
+            _ => None,
+        }
+    }
+}
+
+/// Splits a commit message into its subject and body.
+///
+/// The subject is the first line. The body is everything after the blank
+/// line that should follow it, or everything after the subject when that
+/// blank line is missing.
+pub fn split_message(message: &str) -> (&str, &str) {
+    let (subject, rest) = message.split_once('\n').unwrap_or((message, ""));
+    let subject = subject.strip_suffix('\r').unwrap_or(subject);
+
+    let body = match rest.split_once('\n') {
+        Some((first, after)) if first.trim_end_matches('\r').is_empty() => after,
+        None if rest.trim_end_matches('\r').is_empty() => "",
+        _ => rest,
+    };
+
+    (subject, body)
+}
+
+// How each entry in the generated file list starts.
+const ENTRY_PREFIXES: &[&str] = &[
+    "* In ",
+    "* Renamed ",
+    "* Added binary ",
+    "* Updated binary ",
+    "* Removed binary ",
+];
+
+// e.g. "5 files changed, +120 / -34", over the file list.
+fn stat_line(files: usize, changes: &GitChanges) -> String {
+    let (added, removed) = changes
+        .line_counts
+        .values()
+        .fold((0, 0), |(a, r), (added, removed)| (a + added, r + removed));
+    let noun = if files == 1 { "file" } else { "files" };
+    format!("{} {} changed, +{} / -{}\n\n", files, noun, added, removed)
+}
+
+fn is_stat_line(line: &str) -> bool {
+    line.split_once(" changed, +").is_some_and(|(files, _)| {
+        files
+            .strip_suffix(" files")
+            .or_else(|| files.strip_suffix(" file"))
+            .is_some_and(|count| !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+// e.g. "* Updated binary logo.png".
+fn binary_entry(file: &str, status: FileStatus) -> String {
+    let action = match status {
+        FileStatus::Added => "Added",
+        FileStatus::Deleted => "Removed",
+        _ => "Updated",
+    };
+    format!("* {} binary {}\n", action, file)
+}
+
+// Keywords that introduce a definition's name in common languages.
+const DECLARATION_KEYWORDS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "trait",
+    "class",
+    "def",
+    "function",
+    "interface",
+    "func",
+];
+
+// The name an added line declares, e.g. `parse` from "pub fn parse(input:
+// &str)" or `Parser` from "class Parser(Base):". Comments are skipped.
+fn declared_name(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if ["//", "/*", "*", "#", "--"]
+        .iter()
+        .any(|marker| line.starts_with(marker))
+    {
+        return None;
+    }
+
+    let mut tokens = line.split_whitespace();
+    tokens.find(|token| DECLARATION_KEYWORDS.contains(token))?;
+    let token = tokens.next()?;
+    let end = token
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(token.len());
+    let name = &token[..end];
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        .then_some(name)
+}
+
+// "1 file", "2 files".
+fn counted(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+// Lines such as "- item", "* item" or "2. item".
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return true;
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    digits > 0 && line[digits..].starts_with(". ")
+}
+
+// Parses the trailers in the last paragraph of a body, such as
+// "Fixes: #12" or "Signed-off-by: Name <email>", as (key, value) pairs.
+fn parse_trailers(body: &str) -> Vec<(String, String)> {
+    let paragraph = body.trim_end().rsplit("\n\n").next().unwrap_or("");
+
+    paragraph
+        .lines()
+        .filter_map(parse_trailer)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+// Reads a `Token: value` trailer line, whose token is letters, digits and `-`.
+fn parse_trailer(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let is_token = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_token.then(|| (key, value.trim()))
+}
+
+/// Splits a message into everything before its closing trailer block and
+/// the block itself, such as the `Co-authored-by:` lines a generated message
+/// ends with. The block is empty unless every line of the last paragraph is
+/// a trailer. Sections are parsed from the first part only, so the trailers
+/// never count as part of the last section.
+pub fn split_trailer_block(message: &str) -> (&str, &str) {
+    let trimmed = message.trim_end();
+    let Some((rest, paragraph)) = trimmed.rsplit_once("\n\n") else {
+        return (message, "");
+    };
+    if paragraph.lines().all(|line| parse_trailer(line).is_some()) {
+        (rest, paragraph)
+    } else {
+        (message, "")
+    }
+}
+
+// Appends a trailer block as the message's last paragraph.
+fn with_trailer_block(message: &str, trailers: &str) -> String {
+    if trailers.is_empty() {
+        return message.to_string();
+    }
+    format!("{}\n\n{}\n", message.trim_end(), trailers)
+}
+
+// Reads a line such as "Fixes #12" or "Relates to: #7, #8" as a
+// reference, returning its keyword: everything before the `#`.
+fn reference_keyword(line: &str) -> Option<&str> {
+    let (keyword, rest) = line.split_once('#')?;
+    let keyword = keyword.trim().trim_end_matches(':').trim_end();
+    let after = rest.trim_start_matches(|c: char| c.is_ascii_digit()).trim();
+    let is_reference = !keyword.is_empty()
+        && keyword.split_whitespace().count() <= 3
+        && rest.starts_with(|c: char| c.is_ascii_digit())
+        && (after.is_empty() || after.starts_with(','));
+    is_reference.then_some(keyword)
+}
+
+/// Whether an identity reads "Name <email>", as git writes it.
+pub fn is_name_and_email(value: &str) -> bool {
+    let Some((name, email)) = value
+        .strip_suffix('>')
+        .and_then(|rest| rest.split_once(" <"))
+    else {
+        return false;
+    };
+
+    !name.trim().is_empty()
+        && email
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && !domain.is_empty())
+        && !email.contains(['<', '>', ' '])
+}
+
+// Describes what is wrong with a Conventional Commits header, checking the
+// type, scope, `!` marker, separator and description in that order.
+fn conventional_header_problem(header: &str, types: &[String]) -> Option<String> {
+    let Some((head, description)) = header.split_once(':') else {
+        return Some("missing the ':' after the type, as in 'feat: add login'".to_string());
+    };
+
+    let head = head.strip_suffix('!').unwrap_or(head);
+    let (kind, scope) = match head.split_once('(') {
+        Some((kind, scope)) => match scope.strip_suffix(')') {
+            Some(scope) => (kind, Some(scope)),
+            None => {
+                return Some(
+                    "the scope must be closed with ')' right before the '!' or ':'".to_string(),
+                )
+            }
+        },
+        None => (head, None),
+    };
+
+    if kind.is_empty() {
+        return Some("the type before the ':' is missing".to_string());
+    }
+    if !types.iter().any(|allowed| allowed == kind) {
+        return Some(format!(
+            "type '{}' is not one of: {}",
+            kind,
+            types.join(", ")
+        ));
+    }
+    if scope.is_some_and(|scope| scope.trim().is_empty() || scope.contains(['(', ')'])) {
+        return Some("the scope in parentheses must be a single, non-empty name".to_string());
+    }
+    if !description.starts_with(' ') || description.starts_with("  ") {
+        return Some("the ':' must be followed by exactly one space".to_string());
+    }
+    if description.trim().is_empty() {
+        return Some("the description after ': ' is empty".to_string());
+    }
+    None
+}
+
+// Parses a Conventional Commits footer, `token: value` or `token #value`,
+// where the token uses `-` in place of spaces. `BREAKING CHANGE` is the one
+// token allowed a space, and `BREAKING-CHANGE` is read as the same token.
+fn parse_footer(line: &str) -> Option<(String, String)> {
+    if let Some(value) = line
+        .strip_prefix("BREAKING CHANGE: ")
+        .or_else(|| line.strip_prefix("BREAKING-CHANGE: "))
+    {
+        return Some(("BREAKING CHANGE".to_string(), value.to_string()));
+    }
+
+    let (token, value) = match line.split_once(": ") {
+        Some((token, value)) if !token.contains(' ') => (token, value.to_string()),
+        _ => {
+            let (token, value) = line.split_once(" #")?;
+            (token, format!("#{}", value))
+        }
+    };
+    let is_token =
+        !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    (is_token && !value.trim().is_empty()).then(|| (token.to_string(), value))
+}
+
+// Finds the first `#123`-style issue reference in a commit message.
+fn find_issue_reference(message: &str) -> Option<String> {
+    message.split_whitespace().find_map(|word| {
+        let number = word
+            .trim_start_matches('(')
+            .trim_end_matches(|c: char| !c.is_ascii_digit())
+            .strip_prefix('#')?;
+        (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+            .then(|| format!("#{}", number))
+    })
+}
+
+// Completes the first bare `#` placeholder in the references template, e.g.
+// "Closes #" becomes "Closes #123". Tickets like `PROJ-123` replace the `#`.
+fn fill_reference(template: &str, reference: &str) -> String {
+    let mut filled = false;
+    template
+        .lines()
+        .map(|line| {
+            if !filled && line.ends_with('#') && !line.starts_with('#') {
+                filled = true;
+                match reference.strip_prefix('#') {
+                    Some(number) => format!("{}{}", line, number),
+                    None => format!("{}{}", &line[..line.len() - 1], reference),
+                }
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Extracts the destination path from the paths in a `diff --git` header.
+// Git normally prefixes them with `a/` and `b/`, but `diff.mnemonicPrefix`
+// swaps in `c/`, `i/`, `w/` or `o/`, and `diff.noprefix` drops them entirely,
+// so the prefixes are only stripped when both sides carry one.
+fn parse_diff_header(header: &str) -> Option<String> {
+    if let Some((old, new)) = split_quoted_header(header) {
+        return Some(destination_path(&old, &new));
+    }
+
+    // Both halves name the same path unless the file was renamed, so an
+    // even split recovers paths with spaces, as git itself does
+    if header.len() % 2 == 1 {
+        let middle = header.len() / 2;
+        if header.is_char_boundary(middle) && header[middle..].starts_with(' ') {
+            let (old, new) = (&header[..middle], &header[middle + 1..]);
+            let prefixed = |path: &str| path.as_bytes().get(1) == Some(&b'/');
+            if old == new {
+                return Some(new.to_string());
+            }
+            if prefixed(old) && prefixed(new) && old.get(2..) == new.get(2..) {
+                return new.get(2..).map(|path| path.to_string());
+            }
+        }
+    }
+
+    let mut paths = header.split_whitespace();
+    let old = paths.next()?;
+    let new = paths.next_back().unwrap_or(old);
+    Some(destination_path(old, new))
+}
+
+fn destination_path(old: &str, new: &str) -> String {
+    if old == new {
+        return new.to_string();
+    }
+
+    let strip = |path: &str| -> Option<String> {
+        let (prefix, rest) = path.split_once('/')?;
+        (prefix.len() == 1 && !rest.is_empty()).then(|| rest.to_string())
+    };
+
+    match (strip(old), strip(new)) {
+        (Some(_), Some(new)) => new,
+        _ => new.to_string(),
+    }
+}
+
+// Splits a header in which git quoted either path, as it does for paths
+// holding quotes, backslashes, control or non-ASCII characters. Inside the
+// quotes a `"` is always escaped, so the first unescaped one closes it.
+fn split_quoted_header(header: &str) -> Option<(String, String)> {
+    if let Some(rest) = header.strip_prefix('"') {
+        let mut escaped = false;
+        let end = rest.char_indices().find_map(|(i, c)| {
+            let closes = c == '"' && !escaped;
+            escaped = c == '\\' && !escaped;
+            closes.then_some(i)
+        })?;
+        let (old, new) = header.split_at(end + 2);
+        Some((unquote_path(old), unquote_path(new.trim_start())))
+    } else if header.ends_with('"') {
+        let (old, new) = header.split_at(header.rfind(" \"")?);
+        Some((old.to_string(), unquote_path(new.trim_start())))
+    } else {
+        None
+    }
+}
+
+// Undoes git's C-style quoting of a path, e.g. "docs/caf\303\251.md", whose
+// octal escapes are the bytes of its UTF-8 encoding. Unquoted paths are
+// returned unchanged.
+fn unquote_path(path: &str) -> String {
+    let Some(inner) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut rest = inner.bytes().peekable();
+    while let Some(byte) = rest.next() {
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match rest.next() {
+            Some(b'a') => bytes.push(0x07),
+            Some(b'b') => bytes.push(0x08),
+            Some(b'f') => bytes.push(0x0c),
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'v') => bytes.push(0x0b),
+            Some(digit @ b'0'..=b'7') => {
+                let mut value = u32::from(digit - b'0');
+                while let Some(digit) = rest.next_if(|b| (b'0'..=b'7').contains(b)) {
+                    value = value * 8 + u32::from(digit - b'0');
+                }
+                bytes.push(value as u8);
+            }
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// The advisory for a binary file of `size` bytes, if that's over `limit`.
+fn large_binary_advisory(path: &str, size: u64, limit: u64) -> Option<String> {
+    (size > limit).then(|| {
+        format!(
+            "Large binary file staged: {} ({} bytes, limit {}). Make sure it belongs in the repository.",
+            path, size, limit
+        )
+    })
+}
+
+/// The message after a leading `---` frontmatter block, if there is one.
+pub fn strip_frontmatter(message: &str) -> &str {
+    message
+        .strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+        .map_or(message, |(_, body)| body.trim_start_matches('\n'))
+}
+
+// Reads a dependency name and version from one line of the manifest named
+// `manifest`, e.g. `serde = { version = "1.0" }` in Cargo.toml or
+// `requests>=2.31` in requirements.txt. Lines whose value doesn't look like
+// a version, such as a package's own `edition`, are skipped.
+fn parse_dependency(manifest: &str, line: &str) -> Option<(String, String)> {
+    let versioned =
+        |version: &str| version.starts_with(|c: char| c.is_ascii_digit() || "^~=<>*".contains(c));
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "-_.[]/@".contains(c))
+    };
+    let quoted = |text: &str| text.split('"').nth(1).map(|s| s.to_string());
+    let own_keys = ["version", "edition", "rust-version", "name"];
+
+    // `name = "1.0"` or `name = { version = "1.0", ... }`
+    let toml_entry = |line: &str| {
+        let (key, value) = line.split_once('=')?;
+        let key = key.trim().trim_matches('"');
+        let value = value.trim();
+        let version = match value.strip_prefix('{') {
+            Some(table) => quoted(table.split_once("version")?.1.split_once('=')?.1)?,
+            None => quoted(value)?,
+        };
+        (is_name(key) && !own_keys.contains(&key) && versioned(&version))
+            .then(|| (key.to_string(), version))
+    };
+    // `name>=1.0`, with the operator dropped
+    let requirement = |line: &str| {
+        let line = line.trim_matches(|c: char| c == '"' || c == '\'' || c == ',');
+        let split = line.find(|c: char| "=<>~!".contains(c))?;
+        let name = line[..split].trim();
+        let version = line[split..].trim_start_matches(|c: char| "=<>~!".contains(c) || c == ' ');
+        (is_name(name) && !version.is_empty()).then(|| (name.to_string(), version.to_string()))
+    };
+
+    if line.starts_with('#') || line.starts_with("//") || line.starts_with('-') {
+        return None;
+    }
+    match manifest {
+        "Cargo.toml" => toml_entry(line),
+        "pyproject.toml" => toml_entry(line).or_else(|| requirement(line)),
+        "requirements.txt" => requirement(line),
+        "package.json" => {
+            let (key, value) = line.split_once(':')?;
+            let (key, version) = (quoted(key)?, quoted(value)?);
+            (is_name(&key) && !own_keys.contains(&key.as_str()) && versioned(&version))
+                .then_some((key, version))
+        }
+        "go.mod" => {
+            let mut fields = line
+                .split_whitespace()
+                .skip_while(|field| *field == "require");
+            let (module, version) = (fields.next()?, fields.next()?);
+            (version.starts_with('v') && version[1..].starts_with(|c: char| c.is_ascii_digit()))
+                .then(|| (module.to_string(), version.to_string()))
+        }
+        _ => None,
+    }
+}
+
+// A template section with its placeholder lines replaced by `items`: the
+// heading and `#` guidance lines are kept, then one bullet per item.
+fn with_items(template: &str, items: &[String]) -> String {
+    let guidance = template.lines().take_while(|line| line.starts_with('#'));
+    let bullets = items.iter().map(|item| format!("- {}", item));
+    guidance
+        .map(|line| line.to_string())
+        .chain(bullets)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Files a package or crate keeps at its root.
+const MANIFEST_FILES: &[&str] = &["Cargo.toml", "package.json", "pyproject.toml", "go.mod"];
+
+// Names the component a file belongs to: the innermost directory holding a
+// manifest, such as `core` for a workspace member in `crates/core`, or the
+// top-level directory. Files at the repository root have no scope.
+fn detect_scope(analyzer: &GitDiffAnalyzer, file: &str) -> Option<String> {
+    let path = Path::new(file);
+    let packaged = path
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| {
+            MANIFEST_FILES
+                .iter()
+                .any(|m| analyzer.in_repo(dir.join(m)).is_file())
+        });
+    if let Some(dir) = packaged {
+        return dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+
+    let mut components = path.components();
+    let first = components.next()?;
+    components.next()?;
+    Some(first.as_os_str().to_string_lossy().into_owned())
+}
+
+fn build_globset(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => warn!("Ignoring invalid pattern {:?}: {}", pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        warn!("Failed to build pattern set: {}", e);
+        GlobSet::empty()
+    })
+}
+
+/// Writes commit messages from the changes a [`GitDiffAnalyzer`] found.
+pub struct CommitMessageGenerator<'a> {
+    analyzer: &'a GitDiffAnalyzer,
+}
+
+impl<'a> CommitMessageGenerator<'a> {
+    /// A generator using `analyzer`'s config and repository.
+    pub fn new(analyzer: &'a GitDiffAnalyzer) -> Self {
+        Self { analyzer }
+    }
+
+    fn generate_subject_line(&self, changes: &GitChanges) -> String {
+        let verb = self.analyzer.determine_commit_verb(changes).verb;
+
+        let mut description = self.describe(changes);
+
+        // The verb counts as one of the allowed words
+        if let Some(max) = self.analyzer.config.subject_max_words {
+            let words: Vec<&str> = description.split_whitespace().collect();
+            if words.len() + 1 > max {
+                description = words[..max.saturating_sub(1)].join(" ");
+            }
+        }
+
+        let subject = format!("{} {}", verb, description);
+        let max = self.analyzer.config.subject_max_length;
+        let subject = if subject.chars().count() > max {
+            let kept: String = subject.chars().take(max.saturating_sub(3)).collect();
+            format!("{}...", kept)
+        } else {
+            subject
+        };
+
+        // The gitmoji doesn't count toward the length limit
+        let config = &self.analyzer.config;
+        match config.gitmoji_map.get(&verb).filter(|_| config.gitmoji) {
+            Some(emoji) => format!("{} {}", emoji, subject),
+            None => subject,
+        }
+    }
+
+    // What the subject says after the verb: the names the added lines
+    // declare, e.g. "parse_header and Token", otherwise the module a single
+    // file holds, or how many files changed and where.
+    fn describe(&self, changes: &GitChanges) -> String {
+        let mut names: Vec<&str> = Vec::new();
+        for name in changes
+            .file_changes
+            .values()
+            .flatten()
+            .filter_map(|line| declared_name(line))
+        {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        match names.as_slice() {
+            [name] => return name.to_string(),
+            [first, second] => return format!("{} and {}", first, second),
+            [first, rest @ ..] if !rest.is_empty() => {
+                return format!("{} and {} more", first, rest.len())
+            }
+            _ => {}
+        }
+
+        let files: BTreeSet<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_removals.keys())
+            .chain(changes.file_status.keys())
+            .collect();
+        let dirs: BTreeSet<Option<&str>> = files
+            .iter()
+            .map(|file| file.rsplit_once('/').map(|(dir, _)| dir))
+            .collect();
+        match (files.iter().next(), files.len(), dirs.iter().next()) {
+            (None, _, _) => "codebase".to_string(),
+            (Some(file), 1, _) => Path::new(file.as_str()).file_stem().map_or_else(
+                || file.to_string(),
+                |stem| stem.to_string_lossy().into_owned(),
+            ),
+            (_, count, Some(Some(dir))) if dirs.len() == 1 => {
+                format!("{} in {}", counted(count, "file"), dir)
+            }
+            (_, count, _) => counted(count, "file"),
+        }
+    }
+
+    /// e.g. "3 files (2 modified, 1 added), 14 lines added, 3 removed, verb
+    /// Fix, no breaking changes"
+    pub fn diff_summary(&self, changes: &GitChanges) -> String {
+        let mut files: Vec<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_status.keys())
+            .collect();
+        files.sort();
+        files.dedup();
+
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for status in changes.file_status.values() {
+            *counts.entry(status.label()).or_default() += 1;
+        }
+        let statuses: Vec<String> = counts
+            .iter()
+            .map(|(label, count)| format!("{} {}", count, label))
+            .collect();
+
+        let lines: usize = changes.file_changes.values().map(|lines| lines.len()).sum();
+        let removed: usize = changes
+            .file_removals
+            .values()
+            .map(|lines| lines.len())
+            .sum();
+        let verb = self.analyzer.determine_commit_verb(changes).verb;
+        let breaking = match changes.breaking_changes.len() {
+            0 => "no breaking changes".to_string(),
+            n => counted(n, "breaking change"),
+        };
+
+        format!(
+            "{}{}, {} added, {} removed, verb {}, {}",
+            counted(files.len(), "file"),
+            if statuses.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", statuses.join(", "))
+            },
+            counted(lines, "line"),
+            removed,
+            verb,
+            breaking
+        )
+    }
+
+    /// A Keep a Changelog entry for `changes`: a heading for the section the
+    /// verb maps to in `changelog_sections`, "Changed" otherwise, over the
+    /// subject as its item.
+    pub fn changelog_entry(&self, changes: &GitChanges) -> String {
+        let verb = self.analyzer.determine_commit_verb(changes).verb;
+        let section = self
+            .analyzer
+            .config
+            .changelog_sections
+            .get(&verb)
+            .map_or("Changed", String::as_str);
+
+        format!(
+            "### {}\n\n- {}\n",
+            section,
+            self.generate_subject_line(changes)
+        )
+    }
+
+    /// The trailers the generated message would carry, one "Key: value" per
+    /// line, with `signoff` naming who signs it off.
+    pub fn generate_trailers(&self, changes: &GitChanges, signoff: Option<&str>) -> String {
+        let mut trailers = Vec::new();
+        if let Some(reference) = &changes.reference {
+            trailers.push(format!("Closes: {}", reference));
+        }
+        for author in &changes.co_authors {
+            trailers.push(format!("Co-authored-by: {}", author));
+        }
+        if let Some(identity) = signoff {
+            trailers.push(format!("Signed-off-by: {}", identity));
+        }
+
+        trailers
+            .iter()
+            .map(|trailer| format!("{}\n", trailer))
+            .collect()
+    }
+
+    /// A YAML block between `---` lines. String values are written as JSON
+    /// strings, which YAML reads unchanged, so any subject text stays valid.
+    /// The scope is only set when every file belongs to the same component.
+    pub fn frontmatter(&self, changes: &GitChanges) -> String {
+        let verb = self.analyzer.determine_commit_verb(changes).verb;
+        let scopes: BTreeSet<Option<String>> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_status.keys())
+            .map(|file| detect_scope(self.analyzer, file))
+            .collect();
+        let scope = match scopes.iter().collect::<Vec<_>>().as_slice() {
+            [Some(scope)] => serde_json::Value::from(scope.as_str()),
+            _ => serde_json::Value::Null,
+        };
+        let references: Vec<&str> = changes.reference.iter().map(|r| r.as_str()).collect();
+
+        format!(
+            "---\nverb: {}\nscope: {}\nbreaking: {}\nreferences: {}\n---\n\n",
+            serde_json::Value::from(verb),
+            scope,
+            !changes.breaking_changes.is_empty(),
+            serde_json::Value::from(references)
+        )
+    }
+
+    // Wraps each line over the limit on its own, keeping its indent and
+    // hanging any continuation under a list marker's text. Shorter lines,
+    // line breaks and fenced code blocks are left exactly as they are.
+    fn wrap_body_text(&self, text: &str) -> String {
+        let config = &self.analyzer.config;
+        if !config.wrap_generated_body {
+            return text.to_string();
+        }
+
+        let width = config.body_max_line_length;
+        let mut fenced = false;
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| {
+                if line.trim_start().starts_with("```") {
+                    fenced = !fenced;
+                    return line.to_string();
+                }
+                if fenced || line.chars().count() <= width {
+                    return line.to_string();
+                }
+
+                let content = line.trim_start();
+                let indent = &line[..line.len() - content.len()];
+                let marker = ["- ", "* "]
+                    .iter()
+                    .find(|marker| content.starts_with(**marker))
+                    .map_or(0, |marker| marker.len());
+                let hanging = format!("{}{}", indent, " ".repeat(marker));
+                let options = Options::new(width)
+                    .initial_indent(indent)
+                    .subsequent_indent(&hanging);
+                fill(content, options)
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    // With `group_depth` set, files are grouped by their first that many
+    // directories, e.g. `src/api` at depth 2; otherwise by component.
+    fn group_of(&self, file: &str) -> String {
+        match self.analyzer.config.group_depth {
+            Some(depth) => {
+                let parts: Vec<&str> = file.split('/').collect();
+                let dirs = &parts[..parts.len() - 1];
+                dirs[..depth.min(dirs.len())].join("/")
+            }
+            None => detect_scope(self.analyzer, file).unwrap_or_default(),
+        }
+    }
+
+    // Lists the test files the change touches, by `test_file_patterns`, or
+    // asks for tests when only source files changed. Otherwise the template
+    // is left for the author.
+    fn testing_section(&self, changes: &GitChanges) -> String {
+        let config = &self.analyzer.config;
+        let template = &config.message_template.testing_section;
+        let tests = build_globset(&config.test_file_patterns);
+        let sources = build_globset(&config.source_file_patterns);
+
+        let files: BTreeSet<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_removals.keys())
+            .chain(changes.file_status.keys())
+            .collect();
+        let touched: Vec<String> = files
+            .iter()
+            .filter(|file| tests.is_match(file))
+            .map(|file| format!("Tests changed in {}", file))
+            .collect();
+
+        if !touched.is_empty() {
+            with_items(template, &touched)
+        } else if files.iter().any(|file| sources.is_match(file)) {
+            with_items(
+                template,
+                &["[Required] Add tests for the changed source files".to_string()],
+            )
+        } else {
+            template.clone()
+        }
+    }
+
+    /// A full message for `changes`: the subject, then each template section,
+    /// ending with a newline.
+    pub fn generate_message(&self, changes: &GitChanges) -> String {
+        let mut parts = self
+            .message_parts(changes)
+            .into_iter()
+            .map(|(_, part)| part);
+        let subject = parts.next().unwrap_or_default();
+        let sections: Vec<String> = parts.filter(|part| !part.is_empty()).collect();
+        format!("{}\n\n{}\n", subject, sections.join("\n\n"))
+    }
+
+    /// Renders `changes` into a layout such as `{{subject}}\n\n{{changes}}`,
+    /// so sections can be reordered, dropped or given custom headings.
+    /// Placeholders with nothing to show, such as `{{breaking}}` for a diff
+    /// without breaking changes, are removed along with the blank lines
+    /// around them. An unknown placeholder is an error naming the valid ones.
+    pub fn render_template(&self, layout: &str, changes: &GitChanges) -> Result<String, String> {
+        let parts = self.message_parts(changes);
+        let mut rendered = String::new();
+        let mut rest = layout;
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+            let name = rest[start + 2..start + end].trim();
+            let Some((_, part)) = parts.iter().find(|(part_name, _)| *part_name == name) else {
+                let names: Vec<String> = parts
+                    .iter()
+                    .map(|(name, _)| format!("{{{{{}}}}}", name))
+                    .collect();
+                return Err(format!(
+                    "Unknown placeholder {{{{{}}}}} in the template; use one of {}",
+                    name,
+                    names.join(", ")
+                ));
+            };
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(part.trim_end());
+            rest = &rest[start + end + 2..];
+        }
+        rendered.push_str(rest);
+
+        let mut lines: Vec<&str> = Vec::new();
+        for line in rendered.trim().lines() {
+            if !(line.trim().is_empty() && lines.last().is_some_and(|last| last.trim().is_empty()))
+            {
+                lines.push(line);
+            }
+        }
+        Ok(format!("{}\n", lines.join("\n")))
+    }
+
+    // Each piece of a generated message by the name templates refer to it by,
+    // in the default order. Pieces with nothing to show are empty.
+    fn message_parts(&self, changes: &GitChanges) -> Vec<(&'static str, String)> {
+        let subject = self.generate_subject_line(changes);
+        let templates = &self.analyzer.config.message_template;
+
+        let references = match &changes.reference {
+            Some(reference) => fill_reference(&templates.references_section, reference),
+            None => templates.references_section.clone(),
+        };
+        let mut sections = vec![("subject", subject), ("references", references)];
+
+        // The file with the most added lines, ties broken by name
+        let dominant = changes
+            .file_changes
+            .iter()
+            .max_by(|(a, a_lines), (b, b_lines)| a_lines.len().cmp(&b_lines.len()).then(b.cmp(a)))
+            .map(|(file, _)| file);
+
+        let files: BTreeSet<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_removals.keys())
+            .collect();
+        let renamed_from = |file: &str| {
+            changes
+                .renames
+                .iter()
+                .find(|(_, to)| to == file)
+                .map(|(from, _)| from)
+        };
+        let mut entries: Vec<(&str, String)> = Vec::new();
+        for file in &files {
+            let added = changes
+                .file_changes
+                .get(*file)
+                .map_or(&[][..], Vec::as_slice);
+            let removed = changes
+                .file_removals
+                .get(*file)
+                .map_or(&[][..], Vec::as_slice);
+            let mut entry = match (renamed_from(file), changes.file_status.get(*file)) {
+                (Some(from), _) => format!("* Renamed {} -> {}:\n", from, file),
+                (None, Some(status)) => format!("* In {} ({}):\n", file, status.label()),
+                (None, None) => format!("* In {}:\n", file),
+            };
+            for (label, changes_list) in [("Added", added), ("Removed", removed)] {
+                if changes_list.is_empty() {
+                    continue;
+                }
+                entry.push_str(&format!("  {}:\n", label));
+                let shown = self.analyzer.config.changes_per_file;
+                for change in changes_list.iter().take(shown) {
+                    entry.push_str(&format!(
+                        "{}\n",
+                        self.wrap_body_text(&format!("  - {}", change))
+                    ));
+                }
+                if changes_list.len() > shown {
+                    entry.push_str(&format!(
+                        "  - ... and {} more\n",
+                        changes_list.len() - shown
+                    ));
+                }
+            }
+            if self.analyzer.config.include_excerpt && dominant == Some(*file) {
+                entry.push_str("  ```\n");
+                for line in added.iter().take(self.analyzer.config.excerpt_lines) {
+                    entry.push_str(&format!("  {}\n", line));
+                }
+                entry.push_str("  ```\n");
+            }
+            entries.push((file, entry));
+        }
+        // Binaries have no lines to show, only what happened to them
+        for (file, status) in &changes.binary_files {
+            if renamed_from(file).is_none() {
+                entries.push((file, binary_entry(file, *status)));
+            }
+        }
+        // Other files with no changed lines, such as pure renames, are only
+        // known by status
+        for (file, status) in &changes.file_status {
+            let binary = changes.binary_files.contains_key(file) && renamed_from(file).is_none();
+            if !files.contains(file) && !binary {
+                let entry = match renamed_from(file) {
+                    Some(from) => format!("* Renamed {} -> {}\n", from, file),
+                    None => format!("* In {} ({})\n", file, status.label()),
+                };
+                entries.push((file, entry));
+            }
+        }
+        for (from, to) in &changes.renames {
+            if !files.contains(to)
+                && !changes.file_status.contains_key(to)
+                && !changes.binary_files.contains_key(to)
+            {
+                entries.push((to, format!("* Renamed {} -> {}\n", from, to)));
+            }
+        }
+
+        // Status-only files and renames were added after the rest
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut changes_section = format!("{}\n\n", templates.changes_section);
+        if !entries.is_empty() {
+            changes_section.push_str(&stat_line(entries.len(), changes));
+        }
+        let hidden = match self.analyzer.config.max_listed_files {
+            Some(max) if entries.len() > max => entries.split_off(max).len(),
+            _ => 0,
+        };
+        let mut scopes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (file, entry) in &entries {
+            scopes
+                .entry(self.group_of(file))
+                .or_default()
+                .push(entry.clone());
+        }
+        if scopes.len() > 1 {
+            // One sub-heading per group, for the author to describe each
+            let groups: Vec<String> = scopes
+                .iter()
+                .map(|(scope, entries)| {
+                    let heading = if scope.is_empty() { "(root)" } else { scope };
+                    format!("{}:\n{}", heading, entries.concat())
+                })
+                .collect();
+            changes_section.push_str(&groups.join("\n"));
+        } else {
+            for (_, entry) in &entries {
+                changes_section.push_str(entry);
+            }
+        }
+        if hidden > 0 {
+            let noun = if hidden == 1 { "file" } else { "files" };
+            changes_section.push_str(&format!("* ... and {} more {}\n", hidden, noun));
+        }
+        sections.push(("changes", changes_section));
+
+        let mut related = String::new();
+        if !changes.related_commits.is_empty() {
+            let commits: Vec<String> = changes
+                .related_commits
+                .iter()
+                .map(|commit| format!("- {}", commit))
+                .collect();
+            related = format!("Related recent commits:\n{}", commits.join("\n"));
+        }
+        sections.push(("related", related));
+
+        let mut breaking = String::new();
+        if !changes.breaking_changes.is_empty() {
+            breaking = format!("{}\n\n", templates.breaking_section);
+            for change in &changes.breaking_changes {
+                breaking.push_str(&format!("{}\n", self.wrap_body_text(change)));
+            }
+        }
+        sections.push(("breaking", breaking));
+
+        sections.push(("testing", self.testing_section(changes)));
+        let dependencies = self.analyzer.dependency_changes(changes);
+        if dependencies.is_empty() {
+            sections.push(("dependencies", templates.dependencies_section.clone()));
+        } else {
+            sections.push((
+                "dependencies",
+                with_items(&templates.dependencies_section, &dependencies),
+            ));
+        }
+
+        let co_authors: Vec<String> = changes
+            .co_authors
+            .iter()
+            .map(|author| format!("Co-authored-by: {}", author))
+            .collect();
+        sections.push(("co_authors", co_authors.join("\n")));
+
+        sections
+    }
+
+    /// Combines a freshly generated message with one the author has started
+    /// editing. The subject, references, testing and dependencies sections,
+    /// and any overview written above the file list, belong to the author and
+    /// are kept; the file list and breaking changes come from the new diff.
+    /// The trailers of both are kept, the existing ones first.
+    pub fn merge_message(&self, existing: &str, generated: &str) -> String {
+        let templates = &self.analyzer.config.message_template;
+        let headings: Vec<&str> = templates
+            .sections()
+            .iter()
+            .map(|t| section_heading(t))
+            .collect();
+        let owned = [
+            section_heading(&templates.references_section),
+            section_heading(&templates.testing_section),
+            section_heading(&templates.dependencies_section),
+        ];
+        let changes_heading = section_heading(&templates.changes_section);
+
+        let (existing, existing_trailers) = split_trailer_block(existing);
+        let (generated, generated_trailers) = split_trailer_block(generated);
+        let mut trailers: Vec<&str> = existing_trailers.lines().collect();
+        for trailer in generated_trailers.lines() {
+            if !trailers.contains(&trailer) {
+                trailers.push(trailer);
+            }
+        }
+
+        let (existing_subject, _) = split_message(existing);
+        let (generated_subject, _) = split_message(generated);
+        let subject = match existing_subject.trim() {
+            "" => generated_subject,
+            s if headings.contains(&s) => generated_subject,
+            s => s,
+        };
+
+        let previous = split_sections(existing, &headings);
+        let kept = |heading: &str| {
+            previous
+                .iter()
+                .find(|(h, _)| *h == heading)
+                .map(|(_, body)| body.clone())
+        };
+
+        let sections: Vec<String> = split_sections(generated, &headings)
+            .into_iter()
+            .map(|(heading, body)| {
+                let body = if owned.contains(&heading) {
+                    kept(heading).unwrap_or(body)
+                } else if heading == changes_heading {
+                    match kept(heading).map(|old| self.overview(&old)) {
+                        Some(overview) if !overview.is_empty() => {
+                            self.with_overview(&body, &overview)
+                        }
+                        _ => body,
+                    }
+                } else {
+                    body
+                };
+                format!("{}\n{}", heading, body.trim_end())
+            })
+            .collect();
+
+        with_trailer_block(
+            &format!("{}\n\n{}\n", subject, sections.join("\n\n")),
+            &trailers.join("\n"),
+        )
+    }
+
+    /// Regenerates the message of a commit being amended. The previous
+    /// message is merged as one the author has been editing, so its
+    /// references and sections survive; a free-form message keeps its
+    /// reference lines and its prose as the overview. The subject is kept
+    /// unless the amended commit touches a different set of files than
+    /// `previous_files`, the ones the last commit touched.
+    pub fn amend_message(
+        &self,
+        previous: &str,
+        generated: &str,
+        changes: &GitChanges,
+        previous_files: &BTreeSet<String>,
+    ) -> String {
+        let templates = &self.analyzer.config.message_template;
+        let headings: Vec<&str> = templates
+            .sections()
+            .iter()
+            .map(|t| section_heading(t))
+            .collect();
+
+        let (message, trailers) = split_trailer_block(previous);
+        let (subject, body) = split_message(message);
+        let structured = body.lines().any(|line| headings.contains(&line.trim_end()));
+        let existing = if structured {
+            message.to_string()
+        } else {
+            let (references, prose): (Vec<&str>, Vec<&str>) = body
+                .lines()
+                .partition(|line| reference_keyword(line).is_some());
+            let mut existing = format!("{}\n\n", subject);
+            let prose = prose.join("\n");
+            if !prose.trim().is_empty() {
+                existing.push_str(&format!(
+                    "{}\n{}\n\n",
+                    section_heading(&templates.changes_section),
+                    prose.trim()
+                ));
+            }
+            if !references.is_empty() {
+                existing.push_str(&format!(
+                    "{}\n{}\n",
+                    section_heading(&templates.references_section),
+                    references.join("\n")
+                ));
+            }
+            existing
+        };
+
+        let merged = self.merge_message(&with_trailer_block(&existing, trailers), generated);
+        let mut files: BTreeSet<String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_removals.keys())
+            .chain(changes.binary_files.keys())
+            .chain(changes.file_status.keys())
+            .cloned()
+            .collect();
+        files.extend(changes.renames.iter().map(|(_, to)| to.clone()));
+        if files == *previous_files {
+            return merged;
+        }
+
+        let (generated_subject, _) = split_message(generated);
+        let (_, merged_body) = split_message(&merged);
+        format!("{}\n\n{}", generated_subject, merged_body)
+    }
+
+    // The author's own text in a changes section: everything above the
+    // generated file list that isn't part of the template.
+    fn overview(&self, changes_body: &str) -> String {
+        let template = &self.analyzer.config.message_template.changes_section;
+        let lines: Vec<&str> = changes_body.lines().collect();
+        let end = (0..lines.len())
+            .find(|&i| {
+                let line = lines[i];
+                let next = lines.get(i + 1).copied().unwrap_or("");
+                let is_entry =
+                    |line: &str| ENTRY_PREFIXES.iter().any(|prefix| line.starts_with(prefix));
+                is_entry(line)
+                    || is_stat_line(line)
+                    || line == "Related recent commits:"
+                    || (line.ends_with(':') && is_entry(next))
+            })
+            .unwrap_or(lines.len());
+
+        lines[..end]
+            .iter()
+            .filter(|line| !template.lines().any(|t| t == **line))
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string()
+    }
+
+    // Inserts the overview between the template comments and the file list.
+    fn with_overview(&self, changes_body: &str, overview: &str) -> String {
+        let template = &self.analyzer.config.message_template.changes_section;
+        let (comments, entries): (Vec<&str>, Vec<&str>) = {
+            let lines: Vec<&str> = changes_body.lines().collect();
+            let split = lines
+                .iter()
+                .position(|line| !template.lines().any(|t| t == *line))
+                .unwrap_or(lines.len());
+            (lines[..split].to_vec(), lines[split..].to_vec())
+        };
+
+        format!(
+            "{}\n{}\n\n{}",
+            comments.join("\n"),
+            overview,
+            entries.join("\n").trim_start()
+        )
+    }
+}
+
+/// Adds `entry`, a `### Section` heading over its items, under the
+/// `## [Unreleased]` heading of a Keep a Changelog file. The items join a
+/// section of the same name if there is one, and the Unreleased heading is
+/// created above the latest release when it's missing.
+pub fn prepend_changelog(changelog: &str, entry: &str) -> String {
+    let (heading, items) = entry.split_once('\n').unwrap_or((entry, ""));
+    let items: Vec<String> = items
+        .trim_matches('\n')
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+    let mut lines: Vec<String> = changelog.lines().map(|line| line.to_string()).collect();
+    let is_release = |line: &str| line.starts_with("## ");
+
+    let start = match lines
+        .iter()
+        .position(|line| line.trim().eq_ignore_ascii_case("## [unreleased]"))
+    {
+        Some(start) => start,
+        None => {
+            let mut at = lines
+                .iter()
+                .position(|line| is_release(line))
+                .unwrap_or(lines.len());
+            if at > 0 && !lines[at - 1].is_empty() {
+                lines.insert(at, String::new());
+                at += 1;
+            }
+            lines.splice(at..at, ["## [Unreleased]".to_string(), String::new()]);
+            at
+        }
+    };
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| is_release(line))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+
+    match lines[start + 1..end]
+        .iter()
+        .position(|line| line.trim() == heading)
+    {
+        Some(offset) => {
+            let mut at = start + 1 + offset + 1;
+            if lines.get(at).is_some_and(|line| line.is_empty()) {
+                at += 1;
+            }
+            lines.splice(at..at, items);
+        }
+        None => {
+            let mut at = start + 1;
+            let mut block = Vec::new();
+            if lines.get(at).is_some_and(|line| line.is_empty()) {
+                at += 1;
+            } else {
+                block.push(String::new());
+            }
+            block.push(heading.to_string());
+            block.push(String::new());
+            block.extend(items);
+            block.push(String::new());
+            lines.splice(at..at, block);
+        }
+    }
+
+    format!("{}\n", lines.join("\n").trim_end())
+}
+
+/// Combines several commit messages into one. The first commit's subject
+/// leads, every subject is listed under its verb, and body paragraphs and
+/// trailers are kept once each, in the order they first appear.
+pub fn squash_messages(messages: &[String]) -> String {
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut paragraphs: Vec<&str> = Vec::new();
+    let mut trailers: Vec<&str> = Vec::new();
+
+    for message in messages {
+        let (subject, body) = split_message(message);
+        let (verb, description) = subject.split_once(' ').unwrap_or((subject, ""));
+        let index = match groups.iter().position(|(v, _)| *v == verb) {
+            Some(index) => index,
+            None => {
+                groups.push((verb, Vec::new()));
+                groups.len() - 1
+            }
+        };
+        let described = &mut groups[index].1;
+        if !description.is_empty() && !described.contains(&description) {
+            described.push(description);
+        }
+
+        let mut body_paragraphs: Vec<&str> = body
+            .split("\n\n")
+            .map(|paragraph| paragraph.trim())
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect();
+        let last = body_paragraphs.last().copied().unwrap_or("");
+        if !last.is_empty() && parse_trailers(last).len() == last.lines().count() {
+            body_paragraphs.pop();
+            for trailer in last.lines() {
+                if !trailers.contains(&trailer) {
+                    trailers.push(trailer);
+                }
+            }
+        }
+        for paragraph in body_paragraphs {
+            if !paragraphs.contains(&paragraph) {
+                paragraphs.push(paragraph);
+            }
+        }
+    }
+
+    let subject = messages
+        .first()
+        .map_or("", |message| split_message(message).0);
+    let mut sections = vec![subject.to_string()];
+    for (verb, descriptions) in &groups {
+        let items: Vec<String> = descriptions
+            .iter()
+            .map(|description| format!("- {}", description))
+            .collect();
+        sections.push(format!("{}:\n{}", verb, items.join("\n")));
+    }
+    sections.extend(paragraphs.iter().map(|paragraph| paragraph.to_string()));
+    if !trailers.is_empty() {
+        sections.push(trailers.join("\n"));
+    }
+
+    format!("{}\n", sections.join("\n\n"))
+}
+
+// Like `Command::output`, but kills the process once `timeout` has passed.
+fn output_with_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes while waiting, so a chatty child never blocks on a
+    // full pipe
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stdout = thread::spawn(move || {
+        let mut buffer = Vec::new();
+        stdout.read_to_end(&mut buffer).map(|_| buffer)
+    });
+    let stderr = thread::spawn(move || {
+        let mut buffer = Vec::new();
+        stderr.read_to_end(&mut buffer).map(|_| buffer)
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "git did not finish within {}s (raise git_timeout_secs if it needs longer)",
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let joined = |handle: thread::JoinHandle<io::Result<Vec<u8>>>| {
+        handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("output reader panicked")))
+    };
+    Ok(Output {
+        status,
+        stdout: joined(stdout)?,
+        stderr: joined(stderr)?,
+    })
+}
+
+// Splits a message file as `git commit` cleans it up: lines starting with
+// `comment_char` and everything from the scissors line `git commit -v`
+// writes are dropped, and so are the blank lines left at the end. Returns
+// the message and the dropped lines. A file without comments is returned
+// as it is, so its final newlines can still be checked.
+fn split_git_comments(message: &str, comment_char: char) -> (String, String) {
+    let scissors = format!(
+        "{} ------------------------ >8 ------------------------",
+        comment_char
+    );
+    let mut kept = Vec::new();
+    let mut comments = Vec::new();
+    let mut lines = message.lines();
+    while let Some(line) = lines.next() {
+        if line == scissors {
+            comments.push(line);
+            comments.extend(lines.by_ref());
+        } else if line.starts_with(comment_char) {
+            comments.push(line);
+        } else {
+            kept.push(line);
+        }
+    }
+    if comments.is_empty() {
+        return (message.to_string(), String::new());
+    }
+
+    let mut cleaned = kept.join("\n").trim_end().to_string();
+    if !cleaned.is_empty() {
+        cleaned.push('\n');
+    }
+    (cleaned, comments.join("\n") + "\n")
+}
+
+// Reads a message file, or stdin for `-`, naming the first bad byte when it
+// isn't UTF-8 rather than failing with a generic IO error.
+fn read_message(file_path: &Path) -> Result<String, String> {
+    let bytes = if file_path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        bytes
+    } else {
+        fs::read(file_path).map_err(|e| format!("Failed to read file: {}", e))?
+    };
+    String::from_utf8(bytes).map_err(|e| {
+        format!(
+            "Commit message is not valid UTF-8 (found invalid byte at offset {}); \
+             re-save it as UTF-8, or transcode it first, e.g. `iconv -f latin1 -t utf-8`",
+            e.utf8_error().valid_up_to()
+        )
+    })
+}
+
+// Escapes `text` for an attribute value. XML 1.0 has no way to write the
+// other C0 control characters, even as references, so they become U+FFFD.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\t' => escaped.push_str("&#9;"),
+            c if c < ' ' => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh, empty directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("scripture-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn verifier() -> CommitMessageVerifier {
+        CommitMessageVerifier::new(Config::default())
+    }
+
+    fn rules(verifier: &CommitMessageVerifier, message: &str) -> Vec<&'static str> {
+        verifier
+            .message_violations(message)
+            .iter()
+            .map(Violation::rule_id)
+            .collect()
+    }
+
+    fn no_changes() -> GitChanges {
+        GitChanges {
+            file_changes: BTreeMap::new(),
+            file_removals: BTreeMap::new(),
+            renames: Vec::new(),
+            binary_files: BTreeMap::new(),
+            line_counts: BTreeMap::new(),
+            breaking_changes: Vec::new(),
+            advisories: Vec::new(),
+            reference: None,
+            file_status: BTreeMap::new(),
+            related_commits: Vec::new(),
+            co_authors: Vec::new(),
+            verb: None,
+        }
+    }
+
+    // Runs git in `dir`, returning what it printed.
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // A fresh repository on `main` with one commit adding README.md.
+    fn temp_repo(name: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        git(&dir, &["init", "-q", "-b", "main"]);
+        git(&dir, &["config", "user.name", "Test"]);
+        git(&dir, &["config", "user.email", "test@example.com"]);
+        git(&dir, &["config", "commit.gpgsign", "false"]);
+        fs::write(dir.join("README.md"), "Readme\n").unwrap();
+        git(&dir, &["add", "README.md"]);
+        git(&dir, &["commit", "-q", "-m", "Add readme"]);
+        dir
+    }
+
+    #[test]
+    fn old_config_is_migrated_with_a_backup() {
+        let dir = temp_dir("migrate");
+        let path = dir.join(".scripture.toml");
+        let old = "subject_max_length = 60\n";
+        fs::write(&path, old).unwrap();
+        fs::write(dir.join(".scripture.toml.bak"), "earlier backup").unwrap();
+
+        let backup = migrate_config(&path).unwrap();
+        let migrated = Config::load(&path).unwrap();
+
+        assert_eq!(backup, dir.join(".scripture.toml.1.bak"));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), old);
+        assert_eq!(
+            fs::read_to_string(dir.join(".scripture.toml.bak")).unwrap(),
+            "earlier backup"
+        );
+        assert_eq!(migrated.version, CONFIG_VERSION);
+        assert_eq!(migrated.subject_max_length, 60);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn custom_message_interpolates_values() {
+        let mut config = Config::default();
+        config.custom_messages.insert(
+            "subject-length".to_string(),
+            "Keep it under {max}, it was {len}".to_string(),
+        );
+        let verifier = CommitMessageVerifier::new(config);
+        let subject = format!("Add {}", "x".repeat(60));
+        let findings = verifier.verify_message(&subject);
+
+        assert_eq!(findings[0].message, "Keep it under 50, it was 64");
+    }
+
+    #[test]
+    fn structured_message_is_verified() {
+        let message: StructuredMessage =
+            serde_json::from_str(r#"{"subject": "Add login.", "body": "Lets users sign in"}"#)
+                .unwrap();
+        let violations = verifier().structured_violations(&message);
+        let ids: Vec<_> = violations.iter().map(Violation::rule_id).collect();
+
+        assert_eq!(ids, ["subject-full-stop"]);
+    }
+
+    #[test]
+    fn unstaged_files_leave_out_staged_ones() {
+        let repo = temp_repo("unstaged");
+        fs::write(repo.join("README.md"), "Readme, edited\n").unwrap();
+        fs::write(repo.join("staged.txt"), "Staged\n").unwrap();
+        git(&repo, &["add", "staged.txt"]);
+        let analyzer = GitDiffAnalyzer::new(Config::default()).with_repo(&repo);
+
+        assert_eq!(
+            analyzer.get_unstaged_files(),
+            Some(vec!["README.md".to_string()])
+        );
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn unknown_verb_lists_descriptions() {
+        let message = verifier().verify_message("Changed the login")[0]
+            .message
+            .clone();
+
+        assert!(message.contains("Fix an issue, e.g. bug, typo, error, misstatement"));
+    }
+
+    #[test]
+    fn diff_headers_without_standard_prefixes() {
+        assert_eq!(
+            parse_diff_header("src/lib.rs src/lib.rs").as_deref(),
+            Some("src/lib.rs")
+        );
+        assert_eq!(
+            parse_diff_header("c/src/lib.rs w/src/lib.rs").as_deref(),
+            Some("src/lib.rs")
+        );
+    }
+
+    #[test]
+    fn unchanged_testing_section_is_flagged() {
+        let config = Config {
+            require_edited_sections: true,
+            ..Config::default()
+        };
+        let testing = config.message_template.testing_section.clone();
+        let verifier = CommitMessageVerifier::new(config);
+        let message = format!("Add login\n\n{}\n", testing);
+
+        assert!(rules(&verifier, &message).contains(&"unedited-section"));
+    }
+
+    #[test]
+    fn branch_reference_comes_from_the_branch_only() {
+        let repo = temp_repo("branch-reference");
+        git(
+            &repo,
+            &[
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Fix the logo\n\nCloses #7",
+            ],
+        );
+        let analyzer = GitDiffAnalyzer::new(Config::default()).with_repo(&repo);
+        git(&repo, &["checkout", "-q", "-b", "feature"]);
+        assert_eq!(analyzer.get_branch_reference(), None);
+
+        git(
+            &repo,
+            &[
+                "commit",
+                "-q",
+                "--allow-empty",
+                "-m",
+                "Add login\n\nRefs #42",
+            ],
+        );
+        git(
+            &repo,
+            &["commit", "-q", "--allow-empty", "-m", "Test login"],
+        );
+        assert_eq!(analyzer.get_branch_reference().as_deref(), Some("#42"));
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn message_without_blank_line_splits_at_first_line() {
+        assert_eq!(
+            split_message("Add login\nLets users sign in\n"),
+            ("Add login", "Lets users sign in\n")
+        );
+        assert_eq!(split_message("Add login\n\nBody"), ("Add login", "Body"));
+    }
+
+    #[test]
+    fn fail_fast_stops_at_first_error() {
+        let config = Config {
+            fail_fast: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "changed things.\nno blank"),
+            ["standard-verb"]
+        );
+        assert!(
+            rules(
+                &CommitMessageVerifier::new(Config::default()),
+                "changed things.\nno blank"
+            )
+            .len()
+                > 1
+        );
+    }
+
+    #[test]
+    fn path_filter_keeps_matching_files() {
+        let analyzer =
+            GitDiffAnalyzer::new(Config::default()).with_path_filter(&["src/**".to_string()]);
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n\
+                    --- a/src/a.rs\n\
+                    +++ b/src/a.rs\n\
+                    @@ -1,0 +1,1 @@\n\
+                    +fn a() {}\n\
+                    diff --git a/docs/a.md b/docs/a.md\n\
+                    --- a/docs/a.md\n\
+                    +++ b/docs/a.md\n\
+                    @@ -1,0 +1,1 @@\n\
+                    +About a\n";
+
+        let changes = analyzer.analyse_diff(diff);
+
+        assert_eq!(
+            changes.file_changes.keys().collect::<Vec<_>>(),
+            ["src/a.rs"]
+        );
+        let statuses = analyzer.analyse_name_status("M\tsrc/a.rs\nM\tdocs/a.md\n");
+        assert_eq!(statuses.keys().collect::<Vec<_>>(), ["src/a.rs"]);
+    }
+
+    #[test]
+    fn description_mood_is_checked() {
+        let config = Config {
+            check_description_mood: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Add fixing the parser"),
+            ["description-mood"]
+        );
+        assert!(rules(&verifier, "Add a fix for the parser").is_empty());
+    }
+
+    #[test]
+    fn long_body_warns_when_limited() {
+        let config = Config {
+            max_body_lines: Some(2),
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+        let findings = verifier.verify_message("Add login\n\none\ntwo\nthree");
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule_id, "body-max-lines");
+        assert!(!findings[0].is_error());
+        assert!(rules(
+            &CommitMessageVerifier::new(Config::default()),
+            "Add login\n\none\ntwo\nthree"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn status_letters_map_to_statuses() {
+        let cases = [
+            ('A', FileStatus::Added),
+            ('M', FileStatus::Modified),
+            ('D', FileStatus::Deleted),
+            ('R', FileStatus::Renamed),
+            ('C', FileStatus::Copied),
+            ('T', FileStatus::Modified),
+        ];
+        for (letter, status) in cases {
+            assert_eq!(FileStatus::from_letter(letter), status);
+        }
+    }
+
+    #[test]
+    fn fix_without_fixes_footer_is_flagged() {
+        let mut config = Config::default();
+        config
+            .required_footers
+            .insert("Fix".to_string(), vec!["Fixes".to_string()]);
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(rules(&verifier, "Fix the login"), ["required-footer"]);
+        assert!(rules(&verifier, "Fix the login\n\nFixes: #12").is_empty());
+        assert!(rules(&verifier, "Add the login").is_empty());
+    }
+
+    #[test]
+    fn wordy_subject_is_flagged() {
+        let config = Config {
+            subject_max_words: Some(3),
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Add the new login page"),
+            ["subject-max-words"]
+        );
+        assert!(rules(&verifier, "Add login page").is_empty());
+    }
+
+    #[test]
+    fn subject_max_words_leaves_room_for_a_description() {
+        let words = |max| Config {
+            subject_max_words: Some(max),
+            ..Config::default()
+        };
+
+        assert!(words(1).check_limits().is_err());
+        assert!(words(2).check_limits().is_ok());
+    }
+
+    #[test]
+    fn env_overrides_are_applied() {
+        let vars = [
+            ("SCRIPTURE_SUBJECT_MAX_LENGTH".to_string(), "60".to_string()),
+            (
+                "SCRIPTURE_STRIP_PREFIXES".to_string(),
+                r#"["[wip]"]"#.to_string(),
+            ),
+            ("OTHER_SUBJECT_MAX_LENGTH".to_string(), "10".to_string()),
+        ];
+        let config = Config::default().apply_env(vars.into_iter()).unwrap();
+
+        assert_eq!(config.subject_max_length, 60);
+        assert_eq!(config.strip_prefixes, ["[wip]"]);
+    }
+
+    #[test]
+    fn unparsable_env_override_is_an_error() {
+        let vars = [(
+            "SCRIPTURE_SUBJECT_MAX_LENGTH".to_string(),
+            "long".to_string(),
+        )];
+        let error = Config::default().apply_env(vars.into_iter()).unwrap_err();
+
+        assert!(error.starts_with("Invalid SCRIPTURE_* environment override"));
+    }
+
+    #[test]
+    fn fixer_corrects_what_it_can() {
+        let fixed = verifier().fix_message("changed login.\nbody  \n");
+
+        assert_eq!(fixed, "Changed login\n\nbody\n");
+        assert_eq!(rules(&verifier(), &fixed), ["standard-verb"]);
+    }
+
+    #[test]
+    fn fixer_leaves_git_written_subjects_alone() {
+        let message = "fixup! Add login\n";
+
+        assert_eq!(verifier().fix_message(message), message);
+    }
+
+    #[test]
+    fn fixer_capitalises_the_verb_after_tags() {
+        let config = Config {
+            strip_prefixes: vec!["[skip ci]".to_string()],
+            gitmoji: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            verifier.fix_message(":bug: [skip ci] fix login.\n"),
+            ":bug: [skip ci] Fix login\n"
+        );
+    }
+
+    #[test]
+    fn git_comments_and_scissors_are_dropped() {
+        let file = "Add login\n\n# Please enter the commit message\n\
+                    # ------------------------ >8 ------------------------\n\
+                    diff --git a/x b/x\n";
+        let (message, comments) = split_git_comments(file, '#');
+
+        assert_eq!(message, "Add login\n");
+        assert!(comments.ends_with("diff --git a/x b/x\n"));
+        assert_eq!(
+            split_git_comments("Add login\n\n; note\n", ';').0,
+            "Add login\n"
+        );
+        assert_eq!(split_git_comments("Add login\n\n", '#').0, "Add login\n\n");
+    }
+
+    #[test]
+    fn control_characters_are_flagged() {
+        assert_eq!(
+            rules(&verifier(), "Add login\n\nSee \u{7}here"),
+            ["control-character"]
+        );
+    }
+
+    #[test]
+    fn commit_source_reads_that_commits_diff() {
+        let repo = temp_repo("from-commit");
+        fs::write(repo.join("login.rs"), "fn login() {}\n").unwrap();
+        git(&repo, &["add", "login.rs"]);
+        git(&repo, &["commit", "-q", "-m", "Add login"]);
+        fs::write(repo.join("staged.rs"), "fn staged() {}\n").unwrap();
+        git(&repo, &["add", "staged.rs"]);
+        let analyzer = GitDiffAnalyzer::new(Config::default())
+            .with_repo(&repo)
+            .with_source(DiffSource::Commit("HEAD".to_string()));
+
+        let changes = analyzer.analyse_diff(&analyzer.get_git_diff().unwrap());
+
+        assert_eq!(
+            changes.file_changes.keys().collect::<Vec<_>>(),
+            ["login.rs"]
+        );
+        assert_eq!(changes.file_changes["login.rs"], ["fn login() {}"]);
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn allowlisted_one_word_subject_passes() {
+        let config = Config {
+            standalone_subjects: vec!["Release".to_string()],
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, "Release").is_empty());
+        assert!(!rules(&verifier, "Deploy").is_empty());
+    }
+
+    #[test]
+    fn junit_report_has_a_failure_for_a_violation() {
+        let verifier = verifier();
+        let violations = verifier.message_violations("Add login.");
+        let xml = verifier.junit_report(&[("message".to_string(), violations)]);
+
+        assert!(xml.contains("<failure"));
+        assert!(xml.contains("subject-full-stop"));
+    }
+
+    #[test]
+    fn junit_report_counts_only_enabled_rules() {
+        let report = |verifier: &CommitMessageVerifier, message: &str| {
+            verifier.junit_report(&[("message".to_string(), verifier.message_violations(message))])
+        };
+
+        let xml = report(&verifier(), "Add login\n");
+        let cases = xml.matches("<testcase ").count();
+        assert!(cases < RULES.len());
+        assert!(xml.contains(&format!("tests=\"{}\"", cases)));
+        assert!(!xml.contains("name=\"sign-off\""));
+
+        let signed = CommitMessageVerifier::new(Config {
+            require_sign_off: true,
+            ..Config::default()
+        });
+        let xml = report(&signed, "Add login\n");
+        assert!(xml.contains("name=\"sign-off\""));
+        assert!(xml.contains(&format!("tests=\"{}\"", cases + 1)));
+
+        let xml = report(&verifier(), "");
+        assert!(xml.contains("tests=\"1\" failures=\"1\""));
+    }
+
+    #[test]
+    fn xml_escape_replaces_control_characters() {
+        assert_eq!(
+            xml_escape("a\u{1}b\u{1b}[0m\t<\"&\n"),
+            "a\u{fffd}b\u{fffd}[0m&#9;&lt;&quot;&amp;&#10;"
+        );
+    }
+
+    #[test]
+    fn docs_only_change_with_fix_warns() {
+        let config = Config {
+            check_category_verb: true,
+            ..Config::default()
+        };
+        let verifier =
+            CommitMessageVerifier::new(config).with_file_categories(vec![FileCategory::Docs]);
+        let findings = verifier.verify_message("Fix the readme");
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule_id, "category-verb");
+        assert!(!findings[0].is_error());
+    }
+
+    #[test]
+    fn source_change_without_tests_is_advised() {
+        let analyzer = GitDiffAnalyzer::new(Config {
+            test_advisory: true,
+            ..Config::default()
+        });
+        let diff = "diff --git a/src/login.rs b/src/login.rs\n\
+                    --- a/src/login.rs\n\
+                    +++ b/src/login.rs\n\
+                    @@ -1,0 +1,1 @@\n\
+                    +fn login() {}\n";
+
+        let changes = analyzer.analyse_diff(diff);
+
+        assert_eq!(
+            changes.advisories,
+            ["Source changed without tests: src/login.rs"]
+        );
+    }
+
+    #[test]
+    fn source_change_with_tests_needs_no_advice() {
+        let analyzer = GitDiffAnalyzer::new(Config {
+            test_advisory: true,
+            ..Config::default()
+        });
+        let diff = "diff --git a/src/login.rs b/src/login.rs\n\
+                    --- a/src/login.rs\n\
+                    +++ b/src/login.rs\n\
+                    @@ -1,0 +1,1 @@\n\
+                    +fn login() {}\n\
+                    diff --git a/tests/login_test.rs b/tests/login_test.rs\n\
+                    --- a/tests/login_test.rs\n\
+                    +++ b/tests/login_test.rs\n\
+                    @@ -1,0 +1,1 @@\n\
+                    +fn logs_in() {}\n";
+
+        assert!(analyzer.analyse_diff(diff).advisories.is_empty());
+    }
+
+    #[test]
+    fn changes_are_grouped_by_directory_depth() {
+        let analyzer = GitDiffAnalyzer::new(Config {
+            group_depth: Some(2),
+            ..Config::default()
+        });
+        let mut changes = no_changes();
+        for file in ["crates/a/src/lib.rs", "crates/b/src/lib.rs"] {
+            changes
+                .file_changes
+                .insert(file.to_string(), vec!["let x = 1;".to_string()]);
+            changes
+                .file_status
+                .insert(file.to_string(), FileStatus::Modified);
+        }
+
+        let message = CommitMessageGenerator::new(&analyzer).generate_message(&changes);
+
+        assert!(
+            message.contains("crates/a:\n* In crates/a/src/lib.rs (modified):\n"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("crates/b:\n* In crates/b/src/lib.rs (modified):\n"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn body_line_length_follows_config() {
+        let message = format!("Add login\n\n{}", "word ".repeat(16).trim_end());
+        let config = Config {
+            body_max_line_length: 100,
+            ..Config::default()
+        };
+
+        assert_eq!(rules(&verifier(), &message), ["body-line-length"]);
+        assert!(rules(&CommitMessageVerifier::new(config), &message).is_empty());
+    }
+
+    #[test]
+    fn invalid_utf8_file_is_reported() {
+        let path = std::env::temp_dir().join(format!("scripture-utf8-{}", std::process::id()));
+        fs::write(&path, b"Add caf\xe9\n").unwrap();
+        let error = verifier().file_violations(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert!(error.contains("not valid UTF-8"));
+        assert!(error.contains("offset 7"));
+    }
+
+    #[test]
+    fn related_commits_touch_the_same_files() {
+        let repo = temp_repo("related");
+        for (file, subject) in [
+            ("login.rs", "Add login"),
+            ("other.rs", "Add other"),
+            ("login.rs", "Fix login"),
+        ] {
+            fs::write(repo.join(file), subject).unwrap();
+            git(&repo, &["add", file]);
+            git(&repo, &["commit", "-q", "-m", subject]);
+        }
+        let login = "login.rs".to_string();
+        let subjects = |analyzer: &GitDiffAnalyzer| -> Vec<String> {
+            analyzer
+                .get_related_commits(&[&login])
+                .iter()
+                .map(|line| line.split_once(' ').unwrap().1.to_string())
+                .collect()
+        };
+
+        let analyzer = GitDiffAnalyzer::new(Config::default()).with_repo(&repo);
+        assert_eq!(subjects(&analyzer), ["Fix login", "Add login"]);
+
+        // A commit being described isn't its own history
+        let analyzer = analyzer.with_source(DiffSource::Commit("HEAD".to_string()));
+        assert_eq!(subjects(&analyzer), ["Add login"]);
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn footers_are_parsed() {
+        assert_eq!(
+            parse_footer("Reviewed-by: Jo"),
+            Some(("Reviewed-by".to_string(), "Jo".to_string()))
+        );
+        assert_eq!(
+            parse_footer("Refs #12"),
+            Some(("Refs".to_string(), "#12".to_string()))
+        );
+        assert_eq!(
+            parse_footer("BREAKING CHANGE: drops v1"),
+            Some(("BREAKING CHANGE".to_string(), "drops v1".to_string()))
+        );
+        assert_eq!(parse_footer("Reviewed by: Jo"), None);
+    }
+
+    #[test]
+    fn trailers_come_from_the_last_paragraph() {
+        let body = "Explains why.\n\nFixes: #12\nSigned-off-by: Jo <jo@example.com>";
+
+        assert_eq!(
+            parse_trailers(body),
+            [
+                ("Fixes".to_string(), "#12".to_string()),
+                (
+                    "Signed-off-by".to_string(),
+                    "Jo <jo@example.com>".to_string()
+                ),
+            ]
+        );
+        assert!(parse_trailers("Explains why.").is_empty());
+    }
+
+    #[test]
+    fn malformed_footer_is_flagged() {
+        let config = Config {
+            check_footer_grammar: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, "Add login\n\nWhy.\n\nReviewed-by: Jo").is_empty());
+        assert_eq!(
+            rules(
+                &verifier,
+                "Add login\n\nWhy.\n\nReviewed-by: Jo\nReviewed by: Al"
+            ),
+            ["footer-grammar"]
+        );
+    }
+
+    #[test]
+    fn binaries_over_the_size_limit_are_advised() {
+        let limit = 1024;
+        assert_eq!(large_binary_advisory("logo.png", limit - 1, limit), None);
+        assert_eq!(large_binary_advisory("logo.png", limit, limit), None);
+        assert_eq!(
+            large_binary_advisory("logo.png", limit + 1, limit).as_deref(),
+            Some("Large binary file staged: logo.png (1025 bytes, limit 1024). Make sure it belongs in the repository.")
+        );
+    }
+
+    #[test]
+    fn configured_prefixes_are_stripped() {
+        let config = Config {
+            strip_prefixes: vec!["[skip ci]".to_string()],
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, "[skip ci] Add feature").is_empty());
+        assert!(!rules(
+            &CommitMessageVerifier::new(Config::default()),
+            "[skip ci] Add feature"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn rule_explanation_has_both_examples() {
+        let rule = rule_info("subject-full-stop").unwrap();
+        let text = explain_rule(rule);
+
+        assert!(text.contains(rule.good));
+        assert!(text.contains(rule.bad));
+    }
+
+    #[test]
+    fn merge_keeps_the_authors_subject() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let generated = generator.generate_message(&no_changes());
+        let existing = generated.replacen(split_message(&generated).0, "Add the login page", 1);
+
+        let merged = generator.merge_message(&existing, &generated);
+
+        assert_eq!(split_message(&merged).0, "Add the login page");
+    }
+
+    #[test]
+    fn missing_blank_line_follows_config() {
+        let config = Config {
+            require_blank_after_subject: false,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            rules(&verifier(), "Add login\nLets users sign in"),
+            ["blank-line-after-subject"]
+        );
+        assert!(rules(
+            &CommitMessageVerifier::new(config),
+            "Add login\nLets users sign in"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn excerpt_quotes_the_most_changed_file() {
+        let analyzer = GitDiffAnalyzer::new(Config {
+            include_excerpt: true,
+            excerpt_lines: 3,
+            ..Config::default()
+        });
+        let mut changes = no_changes();
+        changes.file_changes.insert(
+            "src/big.rs".to_string(),
+            (1..=6).map(|n| format!("let line{} = {};", n, n)).collect(),
+        );
+        changes.file_changes.insert(
+            "src/small.rs".to_string(),
+            vec!["let small = 0;".to_string()],
+        );
+
+        let message = CommitMessageGenerator::new(&analyzer).generate_message(&changes);
+
+        assert!(
+            message
+                .contains("  ```\n  let line1 = 1;\n  let line2 = 2;\n  let line3 = 3;\n  ```\n"),
+            "{}",
+            message
+        );
+        assert_eq!(message.matches("```").count(), 2, "{}", message);
+    }
+
+    #[test]
+    fn message_file_needs_one_final_newline() {
+        let ids = |trailing| -> Vec<_> {
+            verifier()
+                .text_violations("Add login", Some(trailing))
+                .iter()
+                .map(Violation::rule_id)
+                .collect()
+        };
+
+        assert_eq!(ids(0), ["final-newline"]);
+        assert!(ids(1).is_empty());
+        assert_eq!(ids(2), ["final-newline"]);
+    }
+
+    #[test]
+    fn generated_and_vendored_files_are_ignored() {
+        let output = "src/schema.rs: linguist-generated: true\n\
+                      src/schema.rs: linguist-vendored: unspecified\n\
+                      vendor/lib.js: linguist-vendored: set\n\
+                      docs/api.txt: linguist-language: Markdown\n\
+                      docs/api.txt: linguist-generated: unset\n\
+                      src/main.rs: linguist-generated: unspecified\n";
+
+        let attributes = parse_linguist_attributes(output);
+
+        assert!(attributes["src/schema.rs"].is_ignored());
+        assert!(attributes["vendor/lib.js"].is_ignored());
+        assert!(!attributes["docs/api.txt"].is_ignored());
+        assert_eq!(
+            attributes["docs/api.txt"].language.as_deref(),
+            Some("Markdown")
+        );
+        assert!(!attributes.contains_key("src/main.rs"));
+    }
+
+    #[test]
+    fn bare_verb_is_flagged() {
+        assert_eq!(rules(&verifier(), "Add"), ["subject-description"]);
+    }
+
+    fn policy(locked: bool) -> VerbPolicy {
+        VerbPolicy {
+            locked,
+            standard_verbs: [("Add", "Create"), ("Fix", "Repair")]
+                .iter()
+                .map(|(verb, description)| (verb.to_string(), description.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn locked_policy_rejects_local_verbs() {
+        let mut config = Config::default();
+        config
+            .standard_verbs
+            .insert("Deploy".to_string(), "Ship it".to_string());
+        let (config, rejected) = config.apply_verb_policy(policy(true));
+
+        assert_eq!(rejected, ["Deploy"]);
+        assert_eq!(
+            config.standard_verbs.keys().collect::<Vec<_>>(),
+            ["Add", "Fix"]
+        );
+        assert_eq!(config.standard_verbs["Fix"], "Repair");
+    }
+
+    #[test]
+    fn open_policy_keeps_local_verbs() {
+        let mut config = Config::default();
+        config
+            .standard_verbs
+            .insert("Deploy".to_string(), "Ship it".to_string());
+        let (config, rejected) = config.apply_verb_policy(policy(false));
+
+        assert!(rejected.is_empty());
+        assert_eq!(
+            config.standard_verbs.keys().collect::<Vec<_>>(),
+            ["Add", "Deploy", "Fix"]
+        );
+    }
+
+    #[test]
+    fn squash_combines_messages() {
+        let squashed = squash_messages(&[
+            "Add login\n\nLets users sign in.\n\nCloses #1".to_string(),
+            "Fix the login button\n\nCloses #1".to_string(),
+        ]);
+
+        assert!(squashed.starts_with("Add login\n"));
+        assert!(squashed.contains("Fix:\n- the login button\n"));
+        assert!(squashed.contains("Lets users sign in."));
+        assert_eq!(squashed.matches("Closes #1").count(), 1);
+    }
+
+    #[test]
+    fn slow_commands_are_killed_at_the_timeout() {
+        let pid_file = std::env::temp_dir().join(format!("scripture-sleep-{}", std::process::id()));
+        let started = Instant::now();
+        let error = output_with_timeout(
+            Command::new("sh")
+                .arg("-c")
+                .arg(format!("echo $$ > {}; exec sleep 5", pid_file.display())),
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        fs::remove_file(&pid_file).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(4));
+        let alive = Command::new("kill")
+            .args(["-0", pid.trim()])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!alive.success());
+    }
+
+    #[test]
+    fn subject_with_two_changes_warns() {
+        let config = Config {
+            check_single_change: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Add login and fix logout"),
+            ["single-change"]
+        );
+        assert!(rules(&verifier, "Add salt and pepper").is_empty());
+    }
+
+    #[test]
+    fn section_files_replace_their_sections() {
+        let dir = temp_dir("section-files");
+        let path = dir.join(".scripture.toml");
+        fs::write(
+            &path,
+            "[message_template]\ntesting_section_file = \"testing.md\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("testing.md"), "# QA [Optional]\nRun the suite\n").unwrap();
+
+        let config = Config::load(&path).unwrap();
+
+        assert_eq!(
+            config.message_template.testing_section,
+            "# QA [Optional]\nRun the suite"
+        );
+        assert_eq!(
+            config.message_template.changes_section,
+            MessageTemplate::default().changes_section
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_section_file_is_an_error() {
+        let dir = temp_dir("missing-section-file");
+        let path = dir.join(".scripture.toml");
+        fs::write(
+            &path,
+            "[message_template]\ntesting_section_file = \"testing.md\"\n",
+        )
+        .unwrap();
+
+        let err = Config::load(&path).unwrap_err();
+
+        assert!(err.starts_with("Failed to read template"), "{}", err);
+        assert!(err.contains("testing.md"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recent_subject_is_flagged_as_a_duplicate() {
+        let verifier = verifier().with_recent_subjects(vec!["Add login ".into()]);
+
+        assert!(rules(&verifier, "Add login\n").contains(&"duplicate-subject"));
+        assert!(rules(&verifier, "Add login  \n").contains(&"duplicate-subject"));
+        assert!(!rules(&verifier, "Add logout\n").contains(&"duplicate-subject"));
+        // Only surrounding whitespace is ignored, not case
+        assert!(!rules(&verifier, "Add Login\n").contains(&"duplicate-subject"));
+    }
+
+    #[test]
+    fn soft_wrap_allows_long_prose_only() {
+        let prose = "word ".repeat(20);
+        let item = format!("- {}", "word ".repeat(20));
+        let config = Config {
+            body_wrap: BodyWrap::Soft,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, &format!("Add login\n\n{}", prose.trim_end())).is_empty());
+        assert_eq!(
+            rules(&verifier, &format!("Add login\n\n{}", item.trim_end())),
+            ["body-line-length"]
+        );
+    }
+
+    #[test]
+    fn diff_summary_counts_files_and_lines() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let mut changes = no_changes();
+        changes.file_changes.insert(
+            "src/login.rs".to_string(),
+            vec!["fn login() {}".to_string(), "fn logout() {}".to_string()],
+        );
+        changes.file_removals.insert(
+            "src/login.rs".to_string(),
+            vec!["fn signin() {}".to_string()],
+        );
+        changes
+            .file_changes
+            .insert("README.md".to_string(), vec!["Log in first".to_string()]);
+        changes
+            .file_status
+            .insert("src/login.rs".to_string(), FileStatus::Modified);
+        changes
+            .file_status
+            .insert("README.md".to_string(), FileStatus::Added);
+        let verb = analyzer.determine_commit_verb(&changes).verb;
+
+        assert_eq!(
+            CommitMessageGenerator::new(&analyzer).diff_summary(&changes),
+            format!(
+                "2 files (1 added, 1 modified), 3 lines added, 1 removed, verb {}, no breaking changes",
+                verb
+            )
+        );
+    }
+
+    #[test]
+    fn unconfigured_reference_keyword_is_flagged() {
+        let config = Config {
+            reference_keywords: vec!["Closes".to_string()],
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Add login\n\nFixes #1"),
+            ["reference-keyword"]
+        );
+        assert!(rules(&verifier, "Add login\n\nCloses #1").is_empty());
+    }
+
+    #[test]
+    fn reference_keyword_is_read_before_the_number() {
+        assert_eq!(reference_keyword("Fixes #12"), Some("Fixes"));
+        assert_eq!(reference_keyword("Relates to: #7, #8"), Some("Relates to"));
+        assert_eq!(reference_keyword("See issue #12 for why we did this"), None);
+        assert_eq!(reference_keyword("Closes #"), None);
+    }
+
+    #[test]
+    fn trailers_only_prints_just_the_trailers() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let mut changes = no_changes();
+        changes.reference = Some("#42".to_string());
+
+        assert_eq!(
+            generator.generate_trailers(&changes, Some("Jo <jo@example.com>")),
+            "Closes: #42\nSigned-off-by: Jo <jo@example.com>\n"
+        );
+    }
+
+    #[test]
+    fn valid_message_passes() {
+        assert!(verifier().verify_message("Add the login page").is_empty());
+    }
+
+    #[test]
+    fn invalid_message_fails() {
+        let findings = verifier().verify_message("added the login page.");
+
+        assert!(findings.iter().any(Finding::is_error));
+    }
+
+    #[test]
+    fn empty_message_fails() {
+        assert_eq!(rules(&verifier(), ""), ["empty-message"]);
+    }
+
+    #[test]
+    fn default_branch_is_detected_in_order() {
+        let repo = temp_repo("default-branch");
+        let analyzer = GitDiffAnalyzer::new(Config::default()).with_repo(&repo);
+        assert_eq!(analyzer.detect_default_branch().as_deref(), Some("main"));
+
+        // init.defaultBranch only counts once that branch exists
+        git(&repo, &["config", "init.defaultBranch", "develop"]);
+        assert_eq!(analyzer.detect_default_branch().as_deref(), Some("main"));
+        git(&repo, &["branch", "develop"]);
+        assert_eq!(analyzer.detect_default_branch().as_deref(), Some("develop"));
+
+        git(
+            &repo,
+            &["update-ref", "refs/remotes/origin/release", "HEAD"],
+        );
+        git(
+            &repo,
+            &[
+                "symbolic-ref",
+                "refs/remotes/origin/HEAD",
+                "refs/remotes/origin/release",
+            ],
+        );
+        assert_eq!(
+            analyzer.detect_default_branch().as_deref(),
+            Some("origin/release")
+        );
+
+        let config = Config {
+            default_branch: Some("stable".to_string()),
+            ..Config::default()
+        };
+        let analyzer = GitDiffAnalyzer::new(config).with_repo(&repo);
+        assert_eq!(analyzer.detect_default_branch().as_deref(), Some("stable"));
+        fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn subject_with_two_sentences_is_flagged() {
+        let config = Config {
+            check_single_sentence: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Add login. Fix logout"),
+            ["single-sentence"]
+        );
+        assert!(rules(&verifier, "Add login for v1.2 users").is_empty());
+    }
+
+    #[test]
+    fn mapped_verbs_are_standard_verbs() {
+        let config = Config::default();
+        for (key, verb) in &config.verb_mapping {
+            assert!(
+                config.standard_verbs.contains_key(verb),
+                "{} maps to {}, which isn't a standard verb",
+                key,
+                verb
+            );
+            assert!(config.indicators.contains_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn conventional_headers_are_checked() {
+        let config = Config {
+            convention: Convention::Conventional,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, "feat(auth): add login").is_empty());
+        assert!(rules(&verifier, "feat!: drop v1 tokens").is_empty());
+        assert_eq!(rules(&verifier, "Feat: add login"), ["conventional-header"]);
+        assert_eq!(rules(&verifier, "feat add login"), ["conventional-header"]);
+    }
+
+    #[test]
+    fn fixer_keeps_conventional_type_lowercase() {
+        let config = Config {
+            convention: Convention::Conventional,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+        let fixed = verifier.fix_message("feat: add login.\n");
+
+        assert_eq!(fixed, "feat: add login\n");
+        assert!(rules(&verifier, &fixed).is_empty());
+    }
+
+    #[test]
+    fn frontmatter_quotes_values_and_can_be_stripped() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let mut changes = no_changes();
+        let file = "my \"app\"/login.rs";
+        changes
+            .file_changes
+            .insert(file.to_string(), vec!["fn login() {}".to_string()]);
+        changes
+            .file_status
+            .insert(file.to_string(), FileStatus::Added);
+        changes.reference = Some("#12".to_string());
+        changes.breaking_changes.push("Remove logout".to_string());
+        let verb = analyzer.determine_commit_verb(&changes).verb;
+
+        let frontmatter = generator.frontmatter(&changes);
+
+        assert_eq!(
+            frontmatter,
+            format!(
+                "---\nverb: \"{}\"\nscope: \"my \\\"app\\\"\"\nbreaking: true\nreferences: [\"#12\"]\n---\n\n",
+                verb
+            )
+        );
+        let message = format!("{}Add login\n", frontmatter);
+        assert_eq!(strip_frontmatter(&message), "Add login\n");
+        assert_eq!(strip_frontmatter("Add login\n"), "Add login\n");
+    }
+
+    #[test]
+    fn leading_digits_count_as_capitalised() {
+        assert!(rules(&verifier(), "Add 3D support").is_empty());
+        assert!(!verifier()
+            .message_violations("3D support fix")
+            .iter()
+            .any(|v| v.rule_id() == "subject-capitalised"));
+    }
+
+    #[test]
+    fn removed_lines_starting_with_dashes_are_counted() {
+        let diff = "diff --git a/q.sql b/q.sql\n\
+                    index 1..2 100644\n\
+                    --- a/q.sql\n\
+                    +++ b/q.sql\n\
+                    @@ -1,2 +1,2 @@\n\
+                    --- old comment\n\
+                    +++ new comment\n \
+                    SELECT 1;\n";
+        let changes = GitDiffAnalyzer::new(Config::default()).analyse_diff(diff);
+
+        assert_eq!(changes.file_removals["q.sql"], ["-- old comment"]);
+        assert_eq!(changes.file_changes["q.sql"], ["++ new comment"]);
+        assert_eq!(changes.line_counts["q.sql"], (1, 1));
+    }
+
+    #[test]
+    fn breaking_indicators_are_whole_words() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+
+        assert!(!analyzer.is_breaking_change("update the changelog"));
+        assert!(analyzer.is_breaking_change("remove the feature"));
+        assert!(!analyzer.is_breaking_change("an unbreakable lock"));
+        assert!(analyzer.is_breaking_change("fn remove_user()"));
+    }
+
+    #[test]
+    fn subject_length_counts_characters() {
+        let config = Config {
+            subject_max_length: 72,
+            ..Config::default()
+        };
+        let wide = CommitMessageVerifier::new(config);
+        let subject = format!("Add {}", "x".repeat(56));
+        assert_eq!(subject.len(), 60);
+        assert!(!rules(&wide, &format!("{}\n", subject)).contains(&"subject-length"));
+
+        let accented = "Add naïve résumé, café, crème brûlée, déjà vu and façade parsing";
+        assert!(accented.len() > 72 && accented.chars().count() > 50);
+        assert!(!rules(&wide, &format!("{}\n", accented)).contains(&"subject-length"));
+        assert!(rules(&verifier(), &format!("{}\n", accented)).contains(&"subject-length"));
+    }
+
+    #[test]
+    fn generated_subject_uses_the_configured_length() {
+        let mut changes = no_changes();
+        let name = "parse_a_rather_long_function_name_for_the_subject";
+        changes.file_changes.insert(
+            "src/parse.rs".to_string(),
+            vec![format!("pub fn {}() {{}}", name)],
+        );
+        changes
+            .file_status
+            .insert("src/parse.rs".to_string(), FileStatus::Modified);
+
+        let analyzer = GitDiffAnalyzer::new(Config {
+            subject_max_length: 72,
+            ..Config::default()
+        });
+        let subject = CommitMessageGenerator::new(&analyzer).generate_subject_line(&changes);
+        assert!(subject.ends_with(name), "{}", subject);
+
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let subject = CommitMessageGenerator::new(&analyzer).generate_subject_line(&changes);
+        assert_eq!(subject.chars().count(), 50);
+        assert!(subject.ends_with("..."), "{}", subject);
+    }
+
+    #[test]
+    fn renames_are_read_from_the_extended_header() {
+        let diff = "diff --git a/old name.rs b/new name.rs\n\
+                    similarity index 90%\n\
+                    rename from old name.rs\n\
+                    rename to new name.rs\n";
+        let changes = GitDiffAnalyzer::new(Config::default()).analyse_diff(diff);
+
+        assert_eq!(
+            changes.renames,
+            [("old name.rs".to_string(), "new name.rs".to_string())]
+        );
+        assert_eq!(
+            changes.breaking_changes,
+            ["* Renamed old name.rs -> new name.rs (90% similar)"]
+        );
+    }
+
+    #[test]
+    fn subject_mood_is_checked() {
+        let config = Config {
+            check_subject_mood: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert!(rules(&verifier, "Fixed login").contains(&"subject-mood"));
+        assert!(rules(&verifier, "Fixing login").contains(&"subject-mood"));
+        assert!(rules(&verifier, "Fix login").is_empty());
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let mut changes = no_changes();
+        for file in ["src/b.rs", "src/a.rs", "README.md"] {
+            changes
+                .file_changes
+                .insert(file.to_string(), vec!["new line".to_string()]);
+            changes
+                .file_status
+                .insert(file.to_string(), FileStatus::Added);
+        }
+
+        assert_eq!(
+            generator.generate_message(&changes),
+            generator.generate_message(&changes)
+        );
+    }
+
+    #[test]
+    fn subject_names_the_added_function() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let diff = "diff --git a/src/parser.rs b/src/parser.rs\n\
+                    --- a/src/parser.rs\n\
+                    +++ b/src/parser.rs\n\
+                    @@ -1,0 +1,3 @@\n\
+                    +pub fn parse_header(input: &str) -> Header {\n\
+                    +    let mut x = vec![];\n\
+                    +}\n";
+        let changes = analyzer.analyse_diff(diff);
+        let verb = analyzer.determine_commit_verb(&changes).verb;
+
+        assert_eq!(
+            CommitMessageGenerator::new(&analyzer).generate_subject_line(&changes),
+            format!("{} parse_header", verb)
+        );
+    }
+
+    #[test]
+    fn binary_hunks_dont_stop_text_files() {
+        let diff = "diff --git a/logo.png b/logo.png\n\
+                    new file mode 100644\n\
+                    index 0000000..1111111\n\
+                    Binary files /dev/null and b/logo.png differ\n\
+                    diff --git a/src/lib.rs b/src/lib.rs\n\
+                    --- a/src/lib.rs\n\
+                    +++ b/src/lib.rs\n\
+                    @@ -1 +1 @@\n\
+                    -fn old() {}\n\
+                    +fn new() {}\n";
+        let changes = GitDiffAnalyzer::new(Config::default()).analyse_diff(diff);
+
+        assert_eq!(changes.binary_files["logo.png"], FileStatus::Added);
+        assert_eq!(changes.file_changes["src/lib.rs"], ["fn new() {}"]);
+        assert_eq!(changes.file_removals["src/lib.rs"], ["fn old() {}"]);
+    }
+
+    #[test]
+    fn trailing_whitespace_is_flagged() {
+        assert_eq!(
+            rules(&verifier(), "Add login\n\nLets users sign in  "),
+            ["trailing-whitespace"]
+        );
+        assert!(rules(&verifier(), "Add login\n\nLets users sign in").is_empty());
+    }
+
+    #[test]
+    fn git_written_subjects_pass() {
+        assert!(rules(&verifier(), "Merge branch 'feature' into main").is_empty());
+        assert!(rules(&verifier(), "fixup! Add login").is_empty());
+        assert!(rules(&verifier(), "Revert \"Add login\"").is_empty());
+    }
+
+    #[test]
+    fn git_written_subjects_can_be_checked() {
+        let config = Config {
+            allow_merge_commits: false,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(
+            rules(&verifier, "Merge branch 'feature'"),
+            ["standard-verb"]
+        );
+        assert_eq!(
+            verifier.fix_message("Merge branch 'feature'\n"),
+            "Merge branch 'feature'\n"
+        );
+    }
+
+    #[test]
+    fn sign_off_is_required() {
+        let config = Config {
+            require_sign_off: true,
+            ..Config::default()
+        };
+        let verifier = CommitMessageVerifier::new(config);
+
+        assert_eq!(rules(&verifier, "Add login"), ["sign-off"]);
+        assert_eq!(
+            rules(&verifier, "Add login\n\nSigned-off-by: Jo"),
+            ["sign-off"]
+        );
+        assert!(rules(&verifier, "Add login\n\nSigned-off-by: Jo <jo@example.com>").is_empty());
+    }
+
+    #[test]
+    fn identities_need_a_name_and_email() {
+        assert!(is_name_and_email("Jo Bloggs <jo@example.com>"));
+        assert!(!is_name_and_email("Jo Bloggs"));
+        assert!(!is_name_and_email("<jo@example.com>"));
+        assert!(!is_name_and_email("Jo <jo>"));
+    }
+
+    #[test]
+    fn trailer_block_is_split_off() {
+        assert_eq!(
+            split_trailer_block("Add login\n\nWhy.\n\nCo-authored-by: Jo <jo@example.com>\n"),
+            ("Add login\n\nWhy.", "Co-authored-by: Jo <jo@example.com>")
+        );
+        assert_eq!(
+            split_trailer_block("Add login\n\n# Dependencies [Optional]\n- none\n").1,
+            ""
+        );
+    }
+
+    #[test]
+    fn co_author_trailers_survive_a_merge() {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let mut changes = no_changes();
+        changes.co_authors = vec!["Al <al@example.com>".to_string()];
+        let existing = generator.generate_message(&changes);
+        changes.co_authors = vec!["Jo <jo@example.com>".to_string()];
+        let generated = generator.generate_message(&changes);
+
+        let merged = generator.merge_message(&existing, &generated);
+
+        assert!(merged.ends_with(
+            "- [ ] External service changes\n\n\
+             Co-authored-by: Al <al@example.com>\n\
+             Co-authored-by: Jo <jo@example.com>\n"
+        ));
+    }
+
+    #[test]
+    fn quoted_paths_are_unquoted() {
+        assert_eq!(unquote_path("\"docs/my file.md\""), "docs/my file.md");
+        assert_eq!(unquote_path("\"docs/t\\303\\251st.md\""), "docs/tést.md");
+        assert_eq!(unquote_path("\"a\\\"b\\\\c\\td\""), "a\"b\\c\td");
+        assert_eq!(unquote_path("plain.rs"), "plain.rs");
+    }
+
+    #[test]
+    fn diff_headers_with_spaces_and_quotes() {
+        assert_eq!(
+            parse_diff_header("a/docs/my file.md b/docs/my file.md").as_deref(),
+            Some("docs/my file.md")
+        );
+        assert_eq!(
+            parse_diff_header("\"a/docs/t\\303\\251st.md\" \"b/docs/t\\303\\251st.md\"").as_deref(),
+            Some("docs/tést.md")
+        );
+    }
+
+    #[test]
+    fn quoted_rename_headers_are_unquoted() {
+        let diff = "diff --git \"a/t\\303\\251st.md\" \"b/docs/t\\303\\251st.md\"\n\
+                    similarity index 100%\n\
+                    rename from \"t\\303\\251st.md\"\n\
+                    rename to \"docs/t\\303\\251st.md\"\n";
+        let changes = GitDiffAnalyzer::new(Config::default()).analyse_diff(diff);
+
+        assert_eq!(
+            changes.renames,
+            [("tést.md".to_string(), "docs/tést.md".to_string())]
+        );
+    }
+
+    #[test]
+    fn closest_verb_is_suggested() {
+        let verifier = verifier();
+
+        assert_eq!(verifier.closest_verb("Fxi").as_deref(), Some("Fix"));
+        assert_eq!(verifier.closest_verb("fix").as_deref(), Some("Fix"));
+        assert_eq!(
+            verifier.closest_verb("Refactr").as_deref(),
+            Some("Refactor")
+        );
+        assert_eq!(verifier.closest_verb("Deploy"), None);
+        assert_eq!(verifier.closest_verb("Ad").as_deref(), Some("Add"));
+    }
+
+    #[test]
+    fn unknown_verb_message_names_the_suggestion() {
+        let message = verifier().verify_message("Fxi login")[0].message.clone();
+
+        assert_eq!(
+            message,
+            "Subject must start with a standard verb, not 'Fxi'; did you mean 'Fix'?"
+        );
+    }
+
+    fn amend(previous: &str, changes: &GitChanges, previous_files: &[&str]) -> String {
+        let analyzer = GitDiffAnalyzer::new(Config::default());
+        let generator = CommitMessageGenerator::new(&analyzer);
+        let generated = generator.generate_message(changes);
+        let previous_files = previous_files.iter().map(|f| f.to_string()).collect();
+        generator.amend_message(previous, &generated, changes, &previous_files)
+    }
+
+    fn changed(files: &[&str]) -> GitChanges {
+        let mut changes = no_changes();
+        for file in files {
+            changes
+                .file_status
+                .insert(file.to_string(), FileStatus::Modified);
+        }
+        changes
+    }
+
+    #[test]
+    fn amend_keeps_references_and_prose() {
+        let amended = amend(
+            "Add the b module\n\nExplains why.\n\nCloses #123\n",
+            &changed(&["src/b.rs"]),
+            &["src/b.rs"],
+        );
+
+        assert!(amended.contains("\nCloses #123\n"));
+        assert!(amended.contains("\nExplains why.\n"));
+    }
+
+    #[test]
+    fn amend_keeps_subject_for_the_same_files() {
+        let amended = amend(
+            "Add the b module\n\nCloses #123\n",
+            &changed(&["src/a.rs", "src/b.rs"]),
+            &["src/b.rs", "src/a.rs"],
+        );
+
+        assert_eq!(split_message(&amended).0, "Add the b module");
+    }
+
+    #[test]
+    fn amend_regenerates_subject_for_other_files() {
+        let amended = amend(
+            "Add the b module\n\nCloses #123\n",
+            &changed(&["src/a.rs", "src/b.rs"]),
+            &["src/b.rs"],
+        );
+
+        assert_ne!(split_message(&amended).0, "Add the b module");
+        assert!(amended.contains("\nCloses #123\n"));
+    }
+}
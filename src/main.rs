@@ -1,396 +1,1301 @@
-use clap::Parser;
-use log::{error, info};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use env_logger::Env;
+use log::{error, info, warn, LevelFilter};
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use textwrap::fill;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode, Stdio};
+
+use scripture::{
+    explain_rule, explain_verb, install_hook, is_name_and_email, load_verb_policy, migrate_config,
+    next_version, prepend_changelog, rule_info, section_heading, split_message, split_sections,
+    split_trailer_block, squash_messages, strip_frontmatter, uninstall_hook,
+    CommitMessageGenerator, CommitMessageVerifier, Config, Convention, DiffSource, Finding,
+    GitDiffAnalyzer, Severity, VerbCandidate, Violation, CONFIG_FILE, CONFIG_VERSION, IGNORE_FILE,
+    RULES, VERB_POLICY_ENV,
+};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Run against the repository at this path instead of the current
+    /// directory, as `git -C` does. Its .scripture.toml and .scriptureignore
+    /// are used unless --config or --ignore-file say otherwise
+    #[arg(short = 'C', long, value_name = "PATH", global = true)]
+    repo: Option<PathBuf>,
+
+    /// Read settings from this TOML or JSON file instead of .scripture.toml
+    #[arg(long = "config", value_name = "PATH")]
+    config_path: Option<PathBuf>,
+
+    /// Leave out the files matched by this gitignore-style file instead of
+    /// .scriptureignore
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Option<PathBuf>,
+
     /// Verify if a commit message follows standards
     #[arg(short = 'm', long = "message")]
     message_string: Option<String>,
 
-    /// Verify if a commit message file follows standards
-    #[arg(short = 'f', long = "file")]
-    message_file: Option<PathBuf>,
-}
+    /// Verify if commit message files follow standards; `-` reads stdin.
+    /// Repeat it or pass several to check a batch
+    #[arg(short = 'f', long = "file", num_args = 1..)]
+    message_file: Vec<PathBuf>,
+
+    /// Refuse to generate a message while any tracked file has unstaged edits
+    #[arg(long)]
+    require_clean_worktree: bool,
+
+    /// Pre-fill the references section with the latest issue referenced on this branch
+    #[arg(long)]
+    inherit_references: bool,
+
+    /// Also fail verification when a template section was left unedited
+    #[arg(long)]
+    require_edited_sections: bool,
+
+    /// Suggest a message for an existing commit from its diff, e.g. when rewording
+    #[arg(long, value_name = "REV")]
+    from_commit: Option<String>,
+
+    /// Describe `git diff <REVSPEC>` instead of the staged changes, e.g. 'HEAD~3..HEAD'
+    #[arg(long, value_name = "REVSPEC", conflicts_with = "from_commit")]
+    range: Option<String>,
+
+    /// Describe the unstaged edits in the working tree
+    #[arg(long, conflicts_with_all = ["from_commit", "range", "staged"])]
+    worktree: bool,
+
+    /// Describe the staged changes, as by default
+    #[arg(long, conflicts_with_all = ["from_commit", "range"])]
+    staged: bool,
+
+    /// Describe the last commit with the staged changes on top, keeping the
+    /// references and sections of its message, as for `git commit --amend`
+    #[arg(long, conflicts_with_all = ["from_commit", "range", "worktree", "merge"])]
+    amend: bool,
+
+    /// Only describe staged files matching these globs, e.g. 'src/**'
+    #[arg(long, num_args = 1.., value_name = "GLOB")]
+    paths: Vec<String>,
+
+    /// Show the generated message through a pager when writing to a terminal
+    #[arg(long)]
+    pager: bool,
+
+    /// Correct fixable problems in the files given with -f before verifying them
+    #[arg(long, requires = "message_file")]
+    fix: bool,
+
+    /// Stop verifying at the first violation instead of reporting them all
+    #[arg(long)]
+    fail_fast: bool,
+
+    /// Output format for verification results and `bump`
+    #[arg(long, value_enum, default_value_t = Format::Human)]
+    format: Format,
+
+    /// Verify a JSON file holding a message as separate `subject` and `body` fields
+    #[arg(long = "json-input", value_name = "FILE")]
+    json_input: Option<PathBuf>,
+
+    /// Verify the messages of the last N commits, naming each that fails
+    #[arg(long, value_name = "N", conflicts_with_all = ["message_string", "message_file", "json_input"])]
+    check_last: Option<usize>,
+
+    /// Warn when the subject verb doesn't suit the kind of files staged
+    #[arg(long)]
+    check_category_verb: bool,
+
+    /// Keep the subject and hand-written sections of an existing output file,
+    /// refreshing only the parts generated from the diff
+    #[arg(long)]
+    merge: bool,
+
+    /// Where to write the generated message; `-` prints it to stdout only
+    #[arg(short = 'o', long, value_name = "PATH", default_value = "commit.md")]
+    output: PathBuf,
+
+    /// Overwrite the output file if it exists, instead of writing beside it
+    #[arg(long)]
+    force: bool,
+
+    /// Render the generated message into this layout file, which places
+    /// sections with placeholders such as {{subject}} and {{changes}}
+    #[arg(long, value_name = "PATH")]
+    template: Option<PathBuf>,
+
+    /// Quote a few added lines from the most changed file
+    #[arg(long)]
+    excerpt: bool,
+
+    /// Leave long lines in the generated body as they are instead of wrapping them
+    #[arg(long)]
+    no_wrap: bool,
+
+    /// Group the listed files by their first this many directories instead
+    /// of by component
+    #[arg(long, value_name = "DEPTH")]
+    group_depth: Option<usize>,
+
+    /// Start generated subjects with the verb's gitmoji, and allow one before
+    /// the verb when verifying
+    #[arg(long)]
+    gitmoji: bool,
+
+    /// Ask which verb to use when the diff fits several about equally well,
+    /// then review each section of the message before it is written
+    #[arg(long)]
+    interactive: bool,
+
+    /// Print a one-line summary of what was found in the diff to stderr
+    #[arg(long)]
+    print_diff_summary: bool,
+
+    /// Print only the trailer block, e.g. for `git interpret-trailers`,
+    /// instead of writing a full message
+    #[arg(long)]
+    trailers_only: bool,
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    standard_verbs: HashMap<String, String>,
-    indicators: HashMap<String, Vec<String>>,
-    verb_mapping: HashMap<String, String>,
-    message_template: MessageTemplate,
+    /// Start the message with a YAML frontmatter block holding the verb,
+    /// scope, breaking flag and references, for tools to parse
+    #[arg(long, conflicts_with = "trailers_only")]
+    frontmatter: bool,
+
+    /// Add a Signed-off-by trailer for the configured git identity
+    #[arg(long, requires = "trailers_only")]
+    signoff: bool,
+
+    /// Credit a co-author with a Co-authored-by trailer; repeat it for each
+    #[arg(long = "co-author", value_name = "NAME <EMAIL>")]
+    co_authors: Vec<String>,
+
+    /// List recent commits touching the same files, for reviewer context
+    #[arg(long)]
+    with_history: bool,
+
+    /// Maximum subject length for this run, overriding the config
+    #[arg(long, visible_alias = "subject-len", value_name = "CHARS")]
+    subject_max: Option<usize>,
+
+    /// Maximum body line length for this run, overriding the config
+    #[arg(long, visible_alias = "body-len", value_name = "CHARS")]
+    body_max: Option<usize>,
+
+    /// Commit message convention to verify against, overriding the config
+    #[arg(long, value_enum)]
+    convention: Option<Convention>,
+
+    /// Print nothing but errors, for scripts that only need the exit code
+    #[arg(short, long, global = true)]
+    quiet: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MessageTemplate {
-    references_section: String,
-    testing_section: String,
-    dependencies_section: String,
-    changes_section: String,
-    breaking_section: String,
+#[derive(Subcommand)]
+enum Commands {
+    /// Rewrite an older config file in the current format
+    MigrateConfig {
+        /// Config file to migrate
+        #[arg(default_value = CONFIG_FILE)]
+        path: PathBuf,
+    },
+    /// Fix what can be fixed in a commit message file, then verify it
+    ///
+    /// Intended for a commit-msg hook: the file is corrected in place and the
+    /// exit code is non-zero only if problems remain.
+    Hook {
+        /// Commit message file, as passed to the commit-msg hook
+        file: PathBuf,
+    },
+    /// Combine the messages of the commits on this branch into one, ready
+    /// for squashing
+    Squash {
+        /// Branch or commit the current branch was started from. Defaults to
+        /// the repository's default branch
+        #[arg(long)]
+        base: Option<String>,
+    },
+    /// Describe a rule, with examples, by the ID shown in verification
+    /// output, or without one, why the staged changes get the verb they do
+    Explain {
+        /// Rule ID, e.g. subject-length
+        rule: Option<String>,
+    },
+    /// Print a shell completion script, e.g. `scripture completions bash`
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Install a commit-msg git hook that runs `scripture hook`
+    InstallHook {
+        /// Replace an existing commit-msg hook that scripture didn't write
+        #[arg(long)]
+        force: bool,
+
+        /// Remove the hook instead
+        #[arg(long, conflicts_with = "force")]
+        uninstall: bool,
+    },
+    /// Turn the staged changes into a Keep a Changelog entry and print it
+    Changelog {
+        /// Add the entry under `## [Unreleased]` in the changelog instead
+        #[arg(long)]
+        write: bool,
+
+        /// Changelog file to update with --write
+        #[arg(long, value_name = "PATH", default_value = "CHANGELOG.md")]
+        file: PathBuf,
+    },
+    /// Print the semantic version bump the staged changes call for: major
+    /// for breaking changes, minor for new capabilities, patch otherwise
+    Bump {
+        /// Current version, to print the next one as well
+        #[arg(long, value_name = "X.Y.Z")]
+        current: Option<String>,
+
+        /// Below 1.0.0, bump the minor version for breaking changes
+        #[arg(long, requires = "current")]
+        zero_minor: bool,
+    },
+    /// Inspect the configuration in effect
+    Config {
+        /// Print the config after applying the file, environment and flags
+        #[arg(long, required = true)]
+        show: bool,
+
+        /// Output format for the printed config
+        #[arg(long, value_enum, default_value_t = ConfigFormat::Toml)]
+        format: ConfigFormat,
+    },
 }
 
-#[derive(Debug)]
-struct GitChanges {
-    file_changes: HashMap<String, Vec<String>>,
-    breaking_changes: Vec<String>,
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Log lines for people
+    Human,
+    /// A JUnit XML report for CI dashboards
+    Junit,
+    /// A JSON object with `valid`, `errors` and `warnings`
+    Json,
 }
 
-impl GitChanges {
-    fn has_changes(&self) -> bool {
-        !self.file_changes.is_empty()
-    }
+#[derive(Clone, Copy, ValueEnum)]
+enum ConfigFormat {
+    Toml,
+    Json,
 }
 
+// Exit codes: 0 when the message is valid or the command succeeded, 1 when
+// verification failed, and 2 when the tool couldn't run, e.g. a bad argument
+// or an unreadable file, matching clap's own usage errors.
+const EXIT_VALID: u8 = 0;
+const EXIT_INVALID: u8 = 1;
+const EXIT_ERROR: u8 = 2;
+
 const COMMIT_INSTRUCTIONS: &str = r#"
 To utilise this commit message:
 
-1. Review the generated commit.md file
+1. Review the generated {path} file
 2. Complete any sections marked with [Required]
 3. Update any sections marked with [Optional]
 4. Use it directly with git commit:
-   git commit -F commit.md
+   git commit -F {path}
 
 Or copy specific sections into your commit:
-   cat commit.md | git commit -F -
+   cat {path} | git commit -F -
 "#;
 
-impl Default for Config {
-    fn default() -> Self {
-        let mut standard_verbs = HashMap::new();
-        standard_verbs.insert(
-            "Add".to_string(),
-            "Create a capability, e.g. feature, test, dependency".to_string(),
-        );
-        standard_verbs.insert(
-            "Cut".to_string(),
-            "Remove a capability, e.g. feature, test, dependency".to_string(),
-        );
-        standard_verbs.insert(
-            "Fix".to_string(),
-            "Fix an issue, e.g. bug, typo, error, misstatement".to_string(),
-        );
-        // ... Add other verbs
-
-        let mut indicators = HashMap::new();
-        indicators.insert(
-            "fix".to_string(),
-            vec!["fix".to_string(), "bug".to_string(), "issue".to_string()],
+// Lists the candidates on `output` and reads the chosen number from `input`.
+// An empty or unreadable answer keeps the first candidate.
+fn pick_verb(candidates: &[VerbCandidate], input: impl BufRead, mut output: impl Write) -> String {
+    let _ = writeln!(output, "The changes fit more than one verb:");
+    for (i, candidate) in candidates.iter().enumerate() {
+        let _ = writeln!(
+            output,
+            "  {}) {} ({} matches: {})",
+            i + 1,
+            candidate.verb,
+            candidate.matches,
+            candidate.words.join(", ")
         );
-        // ... Add other indicators
-
-        let mut verb_mapping = HashMap::new();
-        verb_mapping.insert("fix".to_string(), "Fix".to_string());
-        // ... Add other mappings
-
-        Config {
-            standard_verbs,
-            indicators,
-            verb_mapping,
-            message_template: MessageTemplate {
-                references_section: "# References [Required]\n# Link to related tickets, docs, or discussions\nCloses #\nRelates to #\nSee also: ".to_string(),
-                testing_section: "# Testing Instructions [Optional]\n# Describe how to test these changes\n1. Steps to test\n2. Expected outcomes\n3. Edge cases to verify".to_string(),
-                dependencies_section: "# Dependencies [Optional]\n# List any prerequisite changes or dependencies\n- [ ] Database migrations\n- [ ] Configuration updates\n- [ ] External service changes".to_string(),
-                changes_section: "# Changes Overview [Required]\n# Briefly describe the purpose of these changes".to_string(),
-                breaking_section: "# Breaking Changes [Required if any]\n# List any backward-incompatible changes and migration steps".to_string(),
-            },
-        }
     }
+    let _ = write!(output, "Choose a verb [1]: ");
+    let _ = output.flush();
+
+    let answer = input.lines().next().and_then(|line| line.ok());
+    answer
+        .and_then(|answer| answer.trim().parse::<usize>().ok())
+        .and_then(|choice| candidates.get(choice.wrapping_sub(1)))
+        .unwrap_or(&candidates[0])
+        .verb
+        .clone()
 }
 
-struct CommitMessageVerifier {
-    config: Config,
+// Asks one question and returns the answer line, or None once input ends.
+fn prompt(input: &mut impl BufRead, mut output: impl Write, question: &str) -> Option<String> {
+    let _ = write!(output, "{}", question);
+    let _ = output.flush();
+
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim_end_matches(['\r', '\n']).to_string()),
+    }
 }
 
-impl CommitMessageVerifier {
-    fn new(config: Config) -> Self {
-        Self { config }
+// Walks through the subject and each section in turn, letting the author
+// keep it, replace it, or drop it when it is marked [Optional]. Replacement
+// text is read up to a line holding only ".". Once input ends, everything
+// left is kept as it is.
+fn review_sections(
+    message: &str,
+    headings: &[&str],
+    input: &mut impl BufRead,
+    mut output: impl Write,
+) -> String {
+    let (message, trailers) = split_trailer_block(message);
+    let (subject, body) = split_message(message);
+    let _ = writeln!(output, "Subject: {}", subject);
+    let subject = match prompt(input, &mut output, "[a]ccept or [e]dit? ").as_deref() {
+        Some("e") => prompt(input, &mut output, "New subject: ").unwrap_or(subject.to_string()),
+        _ => subject.to_string(),
+    };
+
+    let mut sections = Vec::new();
+    for (heading, body) in split_sections(body, headings) {
+        let optional = heading.contains("[Optional]");
+        let _ = writeln!(output, "\n{}\n{}", heading, body.trim_end());
+        let question = if optional {
+            "[a]ccept, [e]dit or [s]kip? "
+        } else {
+            "[a]ccept or [e]dit? "
+        };
+        let body = match prompt(input, &mut output, question).as_deref() {
+            Some("s") if optional => continue,
+            Some("e") => {
+                let _ = writeln!(
+                    output,
+                    "Enter the new text, ending with a line holding only \".\":"
+                );
+                let mut lines = Vec::new();
+                while let Some(line) = prompt(input, &mut output, "") {
+                    if line == "." {
+                        break;
+                    }
+                    lines.push(line);
+                }
+                lines.join("\n")
+            }
+            _ => body,
+        };
+        sections.push(format!("{}\n{}", heading, body.trim_end()));
+    }
+    if !trailers.is_empty() {
+        sections.push(trailers.to_string());
     }
 
-    fn verify_message(&self, message: &str) -> (bool, Vec<String>) {
-        let mut errors = Vec::new();
-        let lines: Vec<&str> = message.lines().collect();
+    format!("{}\n\n{}\n", subject, sections.join("\n\n"))
+}
 
-        if lines.is_empty() {
-            return (false, vec!["Empty commit message".to_string()]);
+// Picks a pager the way git does: GIT_PAGER from `vars`, then core.pager,
+// then PAGER, falling back to less. core.pager is only read when needed.
+fn resolve_pager(
+    vars: impl Iterator<Item = (String, String)>,
+    core_pager: impl FnOnce() -> Option<String>,
+) -> String {
+    let (mut git_pager, mut pager) = (None, None);
+    for (name, value) in vars {
+        match name.as_str() {
+            "GIT_PAGER" => git_pager = Some(value),
+            "PAGER" => pager = Some(value),
+            _ => {}
         }
+    }
 
-        let subject = lines[0];
-        if subject.len() > 50 {
-            errors.push("Subject line exceeds 50 characters".to_string());
-        }
+    git_pager
+        .or_else(core_pager)
+        .or(pager)
+        .unwrap_or_else(|| "less".to_string())
+}
 
-        let first_word = subject.split_whitespace().next().unwrap_or("");
-        if !self.config.standard_verbs.contains_key(first_word) {
-            errors.push(format!(
-                "Subject must start with standard verb: {}",
-                self.config
-                    .standard_verbs
-                    .keys()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            ));
-        }
+// Pipes `text` through the user's pager. Returns false when no pager was
+// used, either because stdout is not a terminal or the pager failed to start,
+// so the caller can print the text directly instead.
+fn page_output(text: &str, analyzer: &GitDiffAnalyzer) -> bool {
+    if !std::io::stdout().is_terminal() {
+        return false;
+    }
 
-        if subject.ends_with('.') {
-            errors.push("Subject line ends with a full stop".to_string());
+    let pager = resolve_pager(std::env::vars(), || analyzer.core_pager());
+    if pager.is_empty() || pager == "cat" {
+        return false;
+    }
+
+    let mut child = match Command::new("sh")
+        .args(["-c", &pager])
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to start pager {:?}: {}", pager, e);
+            return false;
         }
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine.
+        let _ = writeln!(stdin, "{}", text);
+    }
+    let _ = child.wait();
+    true
+}
 
-        if !subject.chars().next().map_or(false, |c| c.is_uppercase()) {
-            errors.push("Subject line not capitalised".to_string());
+// Applies the flags that override a config setting for this run.
+fn apply_cli_overrides(cli: &Cli, config: &mut Config) {
+    if cli.require_edited_sections {
+        config.require_edited_sections = true;
+    }
+    if cli.fail_fast {
+        config.fail_fast = true;
+    }
+    if cli.check_category_verb {
+        config.check_category_verb = true;
+    }
+    if cli.excerpt {
+        config.include_excerpt = true;
+    }
+    if cli.no_wrap {
+        config.wrap_generated_body = false;
+    }
+    if cli.gitmoji {
+        config.gitmoji = true;
+    }
+    if cli.group_depth.is_some() {
+        config.group_depth = cli.group_depth;
+    }
+    if let Some(max) = cli.subject_max {
+        config.subject_max_length = max;
+    }
+    if let Some(max) = cli.body_max {
+        config.body_max_line_length = max;
+    }
+    if let Some(convention) = cli.convention {
+        config.convention = convention;
+    }
+}
+
+// The first of `commit-1.md`, `commit-2.md`, ... beside `path` that doesn't
+// exist yet.
+fn next_free_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+    let extension = path
+        .extension()
+        .map_or_else(String::new, |e| format!(".{}", e.to_string_lossy()));
+    (1..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("some numbered name is free")
+}
+
+fn fix_and_verify(verifier: &CommitMessageVerifier, file_path: &Path, prefix: &str) -> u8 {
+    match verifier.fix_file(file_path) {
+        Ok(true) => info!("{}Fixed {}", prefix, file_path.display()),
+        Ok(false) => {}
+        Err(e) => {
+            error!("{}{}", prefix, e);
+            return EXIT_ERROR;
         }
+    }
+    report_verification(verifier, verifier.file_violations(file_path), prefix)
+}
 
-        if lines.get(1).map_or(false, |line| !line.is_empty()) {
-            errors.push("No blank line between subject and body".to_string());
+// Returns the exit code for one message. One that couldn't be read is an
+// error, not a failed verification. `prefix` names the file in a batch.
+fn report_verification(
+    verifier: &CommitMessageVerifier,
+    violations: Result<Vec<Violation>, String>,
+    prefix: &str,
+) -> u8 {
+    let findings = match violations {
+        Ok(violations) => verifier.findings(&violations),
+        Err(e) => {
+            error!("{}{}", prefix, e);
+            return EXIT_ERROR;
+        }
+    };
+    let valid = !findings.iter().any(Finding::is_error);
+    if !valid {
+        error!("{}Commit message validation failed:", prefix);
+    }
+    for finding in findings {
+        if finding.is_error() {
+            error!("{}- {}", prefix, finding);
+        } else {
+            warn!("{}- {}", prefix, finding);
         }
+    }
+    if !valid {
+        return EXIT_INVALID;
+    }
+    info!("{}Commit message is valid", prefix);
+    EXIT_VALID
+}
 
-        for (i, line) in lines.iter().skip(2).enumerate() {
-            if !line.is_empty() && line.len() > 72 {
-                errors.push(format!("Line {} exceeds 72 characters", i + 3));
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // Progress and results are shown by default; --quiet leaves only errors,
+    // whatever RUST_LOG says
+    let mut logger = env_logger::Builder::from_env(Env::default().default_filter_or("info"));
+    if cli.quiet {
+        logger.filter_level(LevelFilter::Error);
+    }
+    logger.init();
+
+    if let Some(Commands::MigrateConfig { path }) = &cli.command {
+        match migrate_config(path) {
+            Ok(backup) => {
+                info!(
+                    "Migrated {} to config version {}",
+                    path.display(),
+                    CONFIG_VERSION
+                );
+                info!("Original saved as {}", backup.display());
+            }
+            Err(e) => {
+                error!("{}", e);
+                return ExitCode::from(EXIT_ERROR);
             }
         }
+        return ExitCode::SUCCESS;
+    }
 
-        (!errors.is_empty(), errors)
+    if let Some(Commands::Completions { shell }) = &cli.command {
+        let mut command = Cli::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(*shell, &mut command, name, &mut std::io::stdout());
+        return ExitCode::SUCCESS;
     }
 
-    fn verify_file(&self, file_path: &PathBuf) -> (bool, Vec<String>) {
-        match fs::read_to_string(file_path) {
-            Ok(message) => self.verify_message(&message),
-            Err(e) => (false, vec![format!("Failed to read file: {}", e)]),
+    if let Some(Commands::Explain { rule: Some(rule) }) = &cli.command {
+        match rule_info(rule) {
+            Some(info) => println!("{}", explain_rule(info)),
+            None => {
+                error!("Unknown rule '{}'", rule);
+                let ids: Vec<&str> = RULES.iter().map(|rule| rule.id).collect();
+                error!("Known rules: {}", ids.join(", "));
+                return ExitCode::from(EXIT_ERROR);
+            }
         }
+        return ExitCode::SUCCESS;
     }
-}
 
-struct GitDiffAnalyzer {
-    config: Config,
-}
+    let repo_root = match &cli.repo {
+        Some(path) if !path.is_dir() => {
+            error!("Repository {} is not a directory", path.display());
+            return ExitCode::from(EXIT_ERROR);
+        }
+        Some(path) => path.clone(),
+        None => PathBuf::new(),
+    };
+    let analyzer_for = |config: Config| match &cli.repo {
+        Some(repo) => GitDiffAnalyzer::new(config).with_repo(repo),
+        None => GitDiffAnalyzer::new(config),
+    };
 
-impl GitDiffAnalyzer {
-    fn new(config: Config) -> Self {
-        Self { config }
-    }
-
-    fn get_git_diff(&self) -> Option<String> {
-        let output = Command::new("git")
-            .args(["diff", "--cached"])
-            .output()
-            .ok()?;
-
-        String::from_utf8(output.stdout).ok()
-    }
-
-    fn analyse_diff(&self, diff_output: &str) -> GitChanges {
-        let mut file_changes = HashMap::new();
-        let mut breaking_changes = Vec::new();
-        let mut current_file = None;
-
-        for line in diff_output.lines() {
-            if line.starts_with("diff --git") {
-                current_file = line
-                    .split_whitespace()
-                    .last()
-                    .map(|s| s.trim_start_matches("b/").to_string());
-            } else if line.starts_with('+') && !line.starts_with("+++") {
-                if let Some(file) = &current_file {
-                    let change = line[1..].trim().to_string();
-                    if !change.is_empty() {
-                        file_changes
-                            .entry(file.clone())
-                            .or_insert_with(Vec::new)
-                            .push(change.clone());
-
-                        if self.is_breaking_change(&change) {
-                            breaking_changes
-                                .push(format!("* Breaking change in {}:\n  {}", file, change));
-                        }
-                    }
-                }
-            }
+    let config_path = match &cli.config_path {
+        Some(path) if !path.exists() => {
+            error!("Config file {} does not exist", path.display());
+            return ExitCode::from(EXIT_ERROR);
         }
+        Some(path) => path.clone(),
+        None => repo_root.join(CONFIG_FILE),
+    };
+    let mut config =
+        match Config::load(&config_path).and_then(|config| config.apply_env(std::env::vars())) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("{}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        };
 
-        GitChanges {
-            file_changes,
-            breaking_changes,
+    if let Ok(path) = std::env::var(VERB_POLICY_ENV) {
+        let policy = match load_verb_policy(Path::new(&path)) {
+            Ok(policy) => policy,
+            Err(e) => {
+                error!("{}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        };
+        let rejected;
+        (config, rejected) = config.apply_verb_policy(policy);
+        for verb in rejected {
+            warn!(
+                "Ignoring verb '{}': the verb policy in {} is locked",
+                verb, path
+            );
         }
     }
 
-    fn is_breaking_change(&self, change: &str) -> bool {
-        let breaking_indicators = [
-            "remove",
-            "delete",
-            "deprecate",
-            "break",
-            "change",
-            "rename",
-            "refactor",
-            "drop",
-            "migrate",
-        ];
+    apply_cli_overrides(&cli, &mut config);
 
-        let change_lower = change.to_lowercase();
-        breaking_indicators
-            .iter()
-            .any(|&word| change_lower.contains(word))
-    }
-
-    fn determine_commit_verb(&self, file_changes: &HashMap<String, Vec<String>>) -> String {
-        let all_changes: String = file_changes
-            .values()
-            .flatten()
-            .map(|s| s.to_lowercase())
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        for (verb, words) in &self.config.indicators {
-            if words.iter().any(|word| all_changes.contains(word)) {
-                return self
-                    .config
-                    .verb_mapping
-                    .get(verb)
-                    .unwrap_or(&"Add".to_string())
-                    .clone();
+    if let Some(Commands::Config { format, .. }) = &cli.command {
+        let printed = match format {
+            ConfigFormat::Toml => toml::to_string_pretty(&config).map_err(|e| e.to_string()),
+            ConfigFormat::Json => serde_json::to_string_pretty(&config).map_err(|e| e.to_string()),
+        };
+        match printed {
+            Ok(printed) => println!("{}", printed),
+            Err(e) => {
+                error!("Failed to serialise config: {}", e);
+                return ExitCode::from(EXIT_ERROR);
             }
         }
-
-        "Add".to_string()
+        return ExitCode::SUCCESS;
     }
-}
-
-struct CommitMessageGenerator<'a> {
-    analyzer: &'a GitDiffAnalyzer,
-}
 
-impl<'a> CommitMessageGenerator<'a> {
-    fn new(analyzer: &'a GitDiffAnalyzer) -> Self {
-        Self { analyzer }
+    if let Some(Commands::InstallHook { force, uninstall }) = &cli.command {
+        let analyzer = analyzer_for(config);
+        let Some(hooks_dir) = analyzer.hooks_dir() else {
+            error!("Not inside a git repository.");
+            return ExitCode::from(EXIT_ERROR);
+        };
+        let result = if *uninstall {
+            uninstall_hook(&hooks_dir).map(|path| format!("Removed {}", path.display()))
+        } else {
+            install_hook(&hooks_dir, *force).map(|path| format!("Installed {}", path.display()))
+        };
+        match result {
+            Ok(done) => info!("{}", done),
+            Err(e) => {
+                error!("{}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        }
+        return ExitCode::SUCCESS;
     }
 
-    fn generate_subject_line(&self, changes: &GitChanges) -> String {
-        let verb = self.analyzer.determine_commit_verb(&changes.file_changes);
+    if let Some(Commands::Squash { base }) = &cli.command {
+        let analyzer = analyzer_for(config);
+        let Some(base) = base.clone().or_else(|| analyzer.detect_default_branch()) else {
+            error!("Could not detect the default branch; pass --base or set default_branch.");
+            return ExitCode::from(EXIT_ERROR);
+        };
+        match analyzer.get_messages_since(&base) {
+            Some(messages) if !messages.is_empty() => print!("{}", squash_messages(&messages)),
+            Some(_) => {
+                error!("No commits on this branch since {}", base);
+                return ExitCode::from(EXIT_ERROR);
+            }
+            None => {
+                error!("Failed to read the commits since {}", base);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
 
-        let significant_changes: Vec<_> = changes
-            .file_changes
-            .values()
-            .filter_map(|changes| changes.first())
-            .collect();
+    let verifying =
+        cli.message_string.is_some() || !cli.message_file.is_empty() || cli.json_input.is_some();
+    let hooked = matches!(cli.command, Some(Commands::Hook { .. }));
+    // Past commits are judged on their own, not against today's staged files
+    // or the history they belong to, so they don't count as `verifying`
+    let auditing = cli.check_last.is_some();
+    let mut verifier = CommitMessageVerifier::new(config.clone());
+    if verifying || hooked {
+        let analyzer = analyzer_for(config.clone());
+        verifier = verifier.with_comment_char(analyzer.comment_char());
+        if config.check_category_verb {
+            verifier = verifier.with_file_categories(analyzer.file_categories());
+        }
+        if let Some(lookback) = config.duplicate_subject_lookback {
+            verifier = verifier.with_recent_subjects(analyzer.get_recent_subjects(lookback));
+        }
+    }
 
-        let description = significant_changes
-            .first()
-            .map(|s| s.trim().to_lowercase())
-            .unwrap_or_else(|| "codebase".to_string());
+    let history = match cli.check_last {
+        Some(count) => match analyzer_for(config.clone()).get_recent_messages(count) {
+            Some(history) => history,
+            None => {
+                error!("Failed to read the last {} commits.", count);
+                return ExitCode::from(EXIT_ERROR);
+            }
+        },
+        None => Vec::new(),
+    };
+    let commit_name =
+        |hash: &str, message: &str| format!("{} {}", hash, message.lines().next().unwrap_or(""));
 
-        let subject = format!("{} {}", verb, description);
-        if subject.len() > 50 {
-            format!("{}...", &subject[..47])
+    if cli.format != Format::Human && (verifying || auditing) {
+        let messages: Vec<(String, Result<Vec<Violation>, String>)> = if auditing {
+            history
+                .iter()
+                .map(|(hash, message)| {
+                    (
+                        commit_name(hash, message),
+                        Ok(verifier.message_violations(message)),
+                    )
+                })
+                .collect()
+        } else if let Some(message) = &cli.message_string {
+            vec![(
+                "message".to_string(),
+                Ok(verifier.message_violations(message)),
+            )]
+        } else if let Some(file_path) = &cli.json_input {
+            vec![(
+                file_path.display().to_string(),
+                verifier.json_file_violations(file_path),
+            )]
         } else {
-            subject
+            cli.message_file
+                .iter()
+                .map(|path| (path.display().to_string(), verifier.file_violations(path)))
+                .collect()
+        };
+
+        let batch = messages.len() > 1;
+        let mut status = EXIT_VALID;
+        let mut suites = Vec::new();
+        for (name, violations) in messages {
+            let report = match violations {
+                Ok(violations) => {
+                    if violations
+                        .iter()
+                        .any(|v| verifier.severity(v) == Severity::Error)
+                    {
+                        status = status.max(EXIT_INVALID);
+                    }
+                    let report = verifier.json_report(&violations);
+                    suites.push((name.clone(), violations));
+                    report
+                }
+                Err(e) => {
+                    status = EXIT_ERROR;
+                    if cli.format != Format::Json {
+                        error!("{}: {}", name, e);
+                        continue;
+                    }
+                    serde_json::json!({ "valid": false, "errors": [e], "warnings": [] })
+                }
+            };
+            // A batch prints one JSON object per line, each naming its file
+            if cli.format == Format::Json {
+                let mut report = report;
+                if batch {
+                    report["file"] = serde_json::Value::from(name);
+                }
+                println!("{}", report);
+            }
+        }
+        if cli.format == Format::Junit {
+            println!("{}", verifier.junit_report(&suites));
         }
+        return ExitCode::from(status);
     }
 
-    fn wrap_body_text(&self, text: &str) -> String {
-        fill(text, 72)
+    if let Some(message) = cli.message_string {
+        return ExitCode::from(report_verification(
+            &verifier,
+            Ok(verifier.message_violations(&message)),
+            "",
+        ));
     }
 
-    fn generate_message(&self, changes: &GitChanges) -> String {
-        let subject = self.generate_subject_line(changes);
-        let templates = &self.analyzer.config.message_template;
-
-        let mut sections = vec![templates.references_section.clone()];
+    if let Some(Commands::Hook { file }) = &cli.command {
+        return ExitCode::from(fix_and_verify(&verifier, file, ""));
+    }
 
-        let mut changes_section = format!("{}\n\n", templates.changes_section);
-        for (file, changes_list) in &changes.file_changes {
-            changes_section.push_str(&format!("* In {}:\n", file));
-            for change in changes_list.iter().take(3) {
-                changes_section.push_str(&format!("  - {}\n", self.wrap_body_text(change)));
+    if auditing {
+        let mut status = EXIT_VALID;
+        let mut valid = 0;
+        for (hash, message) in &history {
+            let prefix = format!("{}: ", commit_name(hash, message));
+            let result =
+                report_verification(&verifier, Ok(verifier.message_violations(message)), &prefix);
+            if result == EXIT_VALID {
+                valid += 1;
             }
+            status = status.max(result);
         }
-        sections.push(changes_section);
+        info!("{} of {} commits valid", valid, history.len());
+        return ExitCode::from(status);
+    }
 
-        if !changes.breaking_changes.is_empty() {
-            let mut breaking = format!("{}\n\n", templates.breaking_section);
-            for change in &changes.breaking_changes {
-                breaking.push_str(&format!("{}\n", self.wrap_body_text(change)));
+    if !cli.message_file.is_empty() {
+        let batch = cli.message_file.len() > 1;
+        let mut status = EXIT_VALID;
+        let mut valid = 0;
+        for file_path in &cli.message_file {
+            let prefix = if batch {
+                format!("{}: ", file_path.display())
+            } else {
+                String::new()
+            };
+            let result = if cli.fix {
+                fix_and_verify(&verifier, file_path, &prefix)
+            } else {
+                report_verification(&verifier, verifier.file_violations(file_path), &prefix)
+            };
+            if result == EXIT_VALID {
+                valid += 1;
             }
-            sections.push(breaking);
+            status = status.max(result);
         }
+        if batch {
+            info!("{} of {} messages valid", valid, cli.message_file.len());
+        }
+        return ExitCode::from(status);
+    }
 
-        sections.push(templates.testing_section.clone());
-        sections.push(templates.dependencies_section.clone());
+    if let Some(file_path) = cli.json_input {
+        return ExitCode::from(report_verification(
+            &verifier,
+            verifier.json_file_violations(&file_path),
+            "",
+        ));
+    }
 
-        format!("{}\n\n{}", subject, sections.join("\n\n"))
+    let ignore_file = match &cli.ignore_file {
+        Some(path) if !path.exists() => {
+            error!("Ignore file {} does not exist", path.display());
+            return ExitCode::from(EXIT_ERROR);
+        }
+        Some(path) => path.clone(),
+        None => repo_root.join(IGNORE_FILE),
+    };
+    let mut analyzer = match analyzer_for(config).with_ignore_file(&ignore_file) {
+        Ok(analyzer) => analyzer,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    if let Some(rev) = &cli.from_commit {
+        analyzer = analyzer.with_source(DiffSource::Commit(rev.clone()));
+    } else if let Some(spec) = &cli.range {
+        analyzer = analyzer.with_source(DiffSource::Range(spec.clone()));
+    } else if cli.worktree {
+        analyzer = analyzer.with_source(DiffSource::Worktree);
+    } else if cli.amend {
+        analyzer = analyzer.with_source(DiffSource::Amend);
     }
-}
+    if !cli.paths.is_empty() {
+        analyzer = analyzer.with_path_filter(&cli.paths);
+    }
+    let generator = CommitMessageGenerator::new(&analyzer);
 
-fn main() {
-    env_logger::init();
-    let cli = Cli::parse();
+    let staged = cli.from_commit.is_none() && cli.range.is_none() && !cli.worktree;
+    if cli.require_clean_worktree && staged {
+        match analyzer.get_unstaged_files() {
+            Some(dirty) if !dirty.is_empty() => {
+                error!("Working tree has unstaged changes:");
+                for file in dirty {
+                    error!("- {}", file);
+                }
+                error!("Stage or stash them so the message matches what will be committed.");
+                return ExitCode::from(EXIT_ERROR);
+            }
+            Some(_) => {}
+            None => {
+                error!("Failed to check the working tree for unstaged changes.");
+                return ExitCode::from(EXIT_ERROR);
+            }
+        }
+    }
 
-    let config = Config::default();
+    let diff_output = match analyzer.get_git_diff() {
+        Ok(diff) => diff,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
 
-    if let Some(message) = cli.message_string {
-        let verifier = CommitMessageVerifier::new(config);
-        let (valid, errors) = verifier.verify_message(&message);
-        if !valid {
-            error!("Commit message validation failed:");
-            for error in errors {
-                error!("- {}", error);
+    let mut changes = analyzer.analyse_diff(&diff_output);
+    if let Some(name_status) = analyzer.get_name_status() {
+        changes.file_status = analyzer.analyse_name_status(&name_status);
+    }
+    if !changes.has_changes() {
+        error!("No changes detected in diff.");
+        return ExitCode::from(EXIT_ERROR);
+    }
+
+    if let Some(Commands::Explain { rule: None }) = &cli.command {
+        let choice = analyzer.determine_commit_verb(&changes);
+        match cli.format {
+            Format::Json => match serde_json::to_string(&choice) {
+                Ok(json) => println!("{}", json),
+                Err(e) => {
+                    error!("Failed to serialise the verb choice: {}", e);
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            },
+            _ => println!("{}", explain_verb(&choice)),
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(Commands::Bump {
+        current,
+        zero_minor,
+    }) = &cli.command
+    {
+        let bump = analyzer.suggest_bump(&changes);
+        let next = match current
+            .as_deref()
+            .map(|v| next_version(v, bump, *zero_minor))
+        {
+            Some(Ok(next)) => Some(next),
+            Some(Err(e)) => {
+                error!("{}", e);
+                return ExitCode::from(EXIT_ERROR);
+            }
+            None => None,
+        };
+        match (cli.format, next) {
+            (Format::Json, next) => {
+                println!(
+                    "{}",
+                    serde_json::json!({ "bump": bump.to_string(), "next": next })
+                )
             }
-            std::process::exit(1);
+            (_, Some(next)) => println!("{} {}", bump, next),
+            (_, None) => println!("{}", bump),
         }
-        info!("Commit message is valid");
-        return;
+        return ExitCode::SUCCESS;
     }
 
-    if let Some(file_path) = cli.message_file {
-        let verifier = CommitMessageVerifier::new(config);
-        let (valid, errors) = verifier.verify_file(&file_path);
-        if !valid {
-            error!("Commit message validation failed:");
-            for error in errors {
-                error!("- {}", error);
+    if let Some(Commands::Changelog { write, file }) = &cli.command {
+        let entry = generator.changelog_entry(&changes);
+        if !write {
+            print!("{}", entry);
+            return ExitCode::SUCCESS;
+        }
+
+        let existing = match fs::read_to_string(file) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "# Changelog\n".to_string(),
+            Err(e) => {
+                error!("Failed to read {}: {}", file.display(), e);
+                return ExitCode::from(EXIT_ERROR);
             }
-            std::process::exit(1);
+        };
+        if let Err(e) = fs::write(file, prepend_changelog(&existing, &entry)) {
+            error!("Failed to write {}: {}", file.display(), e);
+            return ExitCode::from(EXIT_ERROR);
         }
-        info!("Commit message is valid");
-        return;
+        info!("Added the entry to {}", file.display());
+        return ExitCode::SUCCESS;
     }
 
-    let analyzer = GitDiffAnalyzer::new(config);
-    let generator = CommitMessageGenerator::new(&analyzer);
+    changes.advisories.extend(analyzer.binary_advisories());
+    if cli.interactive {
+        if let Some(candidates) = analyzer.ambiguous_verbs(&changes) {
+            let stdin = std::io::stdin();
+            changes.verb = Some(pick_verb(&candidates, stdin.lock(), std::io::stderr()));
+        }
+    }
+    if let Some(author) = cli.co_authors.iter().find(|a| !is_name_and_email(a)) {
+        error!(
+            "Co-author '{}' must be given as 'Name <email>', e.g. 'Jo Bloggs <jo@example.com>'",
+            author
+        );
+        return ExitCode::from(EXIT_ERROR);
+    }
+    changes.co_authors = cli.co_authors.clone();
+    changes.reference = analyzer.get_branch_ticket();
+    if changes.reference.is_none() && cli.inherit_references {
+        changes.reference = analyzer.get_branch_reference();
+    }
+    if cli.trailers_only {
+        let identity = if cli.signoff {
+            match analyzer.get_identity() {
+                Some(identity) => Some(identity),
+                None => {
+                    error!("Set user.name and user.email in git to sign off.");
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            }
+        } else {
+            None
+        };
+        print!(
+            "{}",
+            generator.generate_trailers(&changes, identity.as_deref())
+        );
+        return ExitCode::SUCCESS;
+    }
+    if cli.with_history {
+        let files: Vec<&String> = changes
+            .file_changes
+            .keys()
+            .chain(changes.file_status.keys())
+            .collect();
+        changes.related_commits = analyzer.get_related_commits(&files);
+    }
 
-    let diff_output = match analyzer.get_git_diff() {
-        Some(diff) => diff,
-        None => {
-            error!("No staged changes found. Please stage changes with 'git add' first.");
-            std::process::exit(1);
+    for advisory in &changes.advisories {
+        warn!("{}", advisory);
+    }
+    if cli.print_diff_summary {
+        eprintln!("{}", generator.diff_summary(&changes));
+    }
+
+    let to_stdout = cli.output == Path::new("-");
+    let mut commit_message = match &cli.template {
+        Some(path) => {
+            let rendered = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))
+                .and_then(|layout| generator.render_template(&layout, &changes));
+            match rendered {
+                Ok(message) => message,
+                Err(e) => {
+                    error!("{}", e);
+                    return ExitCode::from(EXIT_ERROR);
+                }
+            }
         }
+        None => generator.generate_message(&changes),
     };
+    if cli.merge && !to_stdout {
+        if let Ok(existing) = fs::read_to_string(&cli.output) {
+            commit_message = generator.merge_message(strip_frontmatter(&existing), &commit_message);
+        }
+    }
+    if cli.amend {
+        let Some(previous) = analyzer.get_last_message() else {
+            error!("There is no commit to amend");
+            return ExitCode::from(EXIT_ERROR);
+        };
+        commit_message = generator.amend_message(
+            &previous,
+            &commit_message,
+            &changes,
+            &analyzer.last_commit_files(),
+        );
+    }
+    if cli.interactive {
+        let stdin = std::io::stdin();
+        let mut input = stdin.lock();
+        let headings: Vec<&str> = analyzer
+            .config()
+            .message_template
+            .sections()
+            .iter()
+            .map(|t| section_heading(t))
+            .collect();
+        loop {
+            commit_message =
+                review_sections(&commit_message, &headings, &mut input, std::io::stderr());
+            let findings = verifier.verify_message(&commit_message);
+            if !findings.iter().any(Finding::is_error) {
+                break;
+            }
+            eprintln!("The message does not pass verification:");
+            for finding in findings {
+                eprintln!("- {}", finding);
+            }
+            let again = prompt(&mut input, std::io::stderr(), "Review it again? [Y/n]: ");
+            if again.is_none_or(|answer| answer.trim().eq_ignore_ascii_case("n")) {
+                break;
+            }
+        }
+    }
+    if cli.frontmatter {
+        commit_message = generator.frontmatter(&changes) + &commit_message;
+    }
 
-    let changes = analyzer.analyse_diff(&diff_output);
-    if !changes.has_changes() {
-        error!("No changes detected in diff.");
-        std::process::exit(1);
+    if to_stdout {
+        print!("{}", commit_message);
+        return ExitCode::SUCCESS;
     }
 
-    let commit_message = generator.generate_message(&changes);
+    // Merging is meant to update the file in place
+    let output = if cli.force || cli.merge || !cli.output.exists() {
+        cli.output.clone()
+    } else {
+        let free = next_free_path(&cli.output);
+        warn!(
+            "{} already exists; writing {} instead (pass --force to overwrite)",
+            cli.output.display(),
+            free.display()
+        );
+        free
+    };
 
-    match fs::write("commit.md", &commit_message) {
+    match fs::write(&output, &commit_message) {
         Ok(_) => {
             info!("\n=== Generated Commit Message ===\n");
-            println!("{}", commit_message);
+            if !(cli.quiet || cli.pager && page_output(&commit_message, &analyzer)) {
+                print!("{}", commit_message);
+            }
             info!("\n===========================");
-            info!("{}", COMMIT_INSTRUCTIONS);
+            info!(
+                "{}",
+                COMMIT_INSTRUCTIONS.replace("{path}", &output.display().to_string())
+            );
         }
         Err(e) => {
             error!("Failed to write commit message: {}", e);
-            std::process::exit(1);
+            return ExitCode::from(EXIT_ERROR);
         }
     }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn candidates() -> Vec<VerbCandidate> {
+        ["Add", "Fix", "Test"]
+            .iter()
+            .map(|verb| VerbCandidate {
+                verb: verb.to_string(),
+                matches: 1,
+                words: vec![verb.to_lowercase()],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pager_is_resolved_in_gits_order() {
+        let vars = |names: &[&str]| -> Vec<(String, String)> {
+            names
+                .iter()
+                .map(|name| (name.to_string(), format!("{}-pager", name)))
+                .collect()
+        };
+        let core = || Some("core-pager".to_string());
+
+        assert_eq!(
+            resolve_pager(vars(&["PAGER", "GIT_PAGER"]).into_iter(), core),
+            "GIT_PAGER-pager"
+        );
+        assert_eq!(
+            resolve_pager(vars(&["PAGER"]).into_iter(), core),
+            "core-pager"
+        );
+        assert_eq!(
+            resolve_pager(vars(&["PAGER"]).into_iter(), || None),
+            "PAGER-pager"
+        );
+        assert_eq!(resolve_pager(vars(&[]).into_iter(), || None), "less");
+    }
+
+    #[test]
+    fn length_flags_override_the_config() {
+        let cli = Cli::parse_from(["scripture", "--subject-max", "72", "--body-len", "100"]);
+        let mut config = Config::default();
+        apply_cli_overrides(&cli, &mut config);
+
+        assert_eq!(config.subject_max_length, 72);
+        assert_eq!(config.body_max_line_length, 100);
+        assert_eq!(config.convention, Config::default().convention);
+    }
+
+    #[test]
+    fn picking_a_listed_verb() {
+        assert_eq!(
+            pick_verb(&candidates(), Cursor::new("2\n"), Vec::new()),
+            "Fix"
+        );
+    }
+
+    #[test]
+    fn picking_an_unlisted_number_keeps_the_first_verb() {
+        assert_eq!(
+            pick_verb(&candidates(), Cursor::new("7\n"), Vec::new()),
+            "Add"
+        );
+        assert_eq!(
+            pick_verb(&candidates(), Cursor::new("0\n"), Vec::new()),
+            "Add"
+        );
+    }
+
+    #[test]
+    fn picking_at_end_of_input_keeps_the_first_verb() {
+        assert_eq!(pick_verb(&candidates(), Cursor::new(""), Vec::new()), "Add");
+    }
+
+    #[test]
+    fn bash_completions_cover_the_flags() {
+        let mut completions = Vec::new();
+        clap_complete::generate(
+            Shell::Bash,
+            &mut Cli::command(),
+            "scripture",
+            &mut completions,
+        );
+        let completions = String::from_utf8(completions).unwrap();
+
+        assert!(completions.contains("scripture"));
+        assert!(completions.contains("--subject-max"));
+    }
+
+    const REVIEWED: &str = "Add login\n\n\
+                            # Changes Overview [Required]\nAdds a login page\n\n\
+                            # Testing Instructions [Optional]\nRun it\n";
+    const HEADINGS: [&str; 2] = [
+        "# Changes Overview [Required]",
+        "# Testing Instructions [Optional]",
+    ];
+
+    fn review(script: &str) -> String {
+        review_sections(REVIEWED, &HEADINGS, &mut Cursor::new(script), Vec::new())
+    }
+
+    #[test]
+    fn accepting_every_section_keeps_the_message() {
+        assert_eq!(review("a\na\na\n"), REVIEWED);
+        assert_eq!(review(""), REVIEWED);
+    }
+
+    #[test]
+    fn editing_replaces_text_up_to_the_dot() {
+        assert_eq!(
+            review("e\nFix login\ne\nFirst line\nSecond line\n.\na\n"),
+            "Fix login\n\n\
+             # Changes Overview [Required]\nFirst line\nSecond line\n\n\
+             # Testing Instructions [Optional]\nRun it\n"
+        );
+    }
+
+    #[test]
+    fn only_optional_sections_can_be_skipped() {
+        assert_eq!(
+            review("a\ns\ns\n"),
+            "Add login\n\n# Changes Overview [Required]\nAdds a login page\n"
+        );
+    }
+
+    #[test]
+    fn skipping_the_last_section_keeps_the_trailers() {
+        let message = "Add login\n\n\
+                       # Testing Instructions [Optional]\nRun it\n\n\
+                       # Dependencies [Optional]\n- none\n\n\
+                       Co-authored-by: Jo <jo@example.com>\n";
+        let headings = [
+            "# Testing Instructions [Optional]",
+            "# Dependencies [Optional]",
+        ];
+        let mut input = Cursor::new("a\na\ns\n");
+
+        assert_eq!(
+            review_sections(message, &headings, &mut input, Vec::new()),
+            "Add login\n\n# Testing Instructions [Optional]\nRun it\n\n\
+             Co-authored-by: Jo <jo@example.com>\n"
+        );
+    }
 }